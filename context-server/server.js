@@ -0,0 +1,901 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+// A Model Context Protocol server that answers questions about a Dhall
+// project with the `dhall` tools. It speaks newline-delimited JSON-RPC over
+// stdio, and the extension starts it with `DHALL_MCP_ROOTS` set to a JSON
+// array of the project's worktree roots, `DHALL_MCP_INDEX` to a JSON file
+// of documentation entries by source, `DHALL_MCP_INSTRUCTIONS` to the Dhall
+// versions the project uses, `DHALL_MCP_PATH` to a JSON array of directories
+// holding the tools the extension installed, and `DHALL_MCP_PERMISSIONS` to
+// what the assistant may do.
+
+"use strict";
+
+const { spawnSync } = require("child_process");
+const fs = require("fs");
+const os = require("os");
+const path = require("path");
+const readline = require("readline");
+
+const ROOTS = JSON.parse(process.env.DHALL_MCP_ROOTS || "[]");
+const ROOT = ROOTS[0] || process.cwd();
+
+// The tools on the `PATH` come first, then the extension's own copies.
+process.env.PATH = [process.env.PATH, ...JSON.parse(process.env.DHALL_MCP_PATH || "[]")]
+  .filter(Boolean)
+  .join(path.delimiter);
+
+// Which tools are enabled (all, if `tools` is unset), and whether remote
+// and environment imports may be resolved. Both are off unless allowed.
+const PERMISSIONS = JSON.parse(process.env.DHALL_MCP_PERMISSIONS || "{}");
+
+// Without `remote_imports`, `dhall` is pointed at a proxy nothing listens
+// on, so pinned imports resolve from the cache and nothing is fetched.
+const PROCESS_ENV = PERMISSIONS.remote_imports
+  ? process.env
+  : {
+      ...process.env,
+      HTTP_PROXY: "http://127.0.0.1:9",
+      HTTPS_PROXY: "http://127.0.0.1:9",
+      http_proxy: "http://127.0.0.1:9",
+      https_proxy: "http://127.0.0.1:9",
+      NO_PROXY: "",
+      no_proxy: "",
+    };
+
+function loadIndex() {
+  try {
+    return JSON.parse(fs.readFileSync(process.env.DHALL_MCP_INDEX, "utf8"));
+  } catch {
+    return {};
+  }
+}
+
+const INDEX = loadIndex();
+
+function run(program, args, input, cwd = ROOT) {
+  const result = spawnSync(program, args, {
+    cwd,
+    env: PROCESS_ENV,
+    input,
+    encoding: "utf8",
+    maxBuffer: 64 * 1024 * 1024,
+  });
+  if (result.error) {
+    throw new Error(`failed to run ${program}: ${result.error.message}`);
+  }
+  if (result.status !== 0) {
+    throw new Error(result.stderr.trim() || `${program} exited with ${result.status}`);
+  }
+  return result.stdout;
+}
+
+// Whether `file` is inside one of the project's worktrees, following
+// symlinks: the permissions only cover the project.
+function insideProject(file) {
+  const real = (file) => {
+    try {
+      return fs.realpathSync(file);
+    } catch {
+      // A file that doesn't exist fails when it's read.
+      return file;
+    }
+  };
+  return (ROOTS.length ? ROOTS : [ROOT]).some((root) => {
+    const relative = path.relative(real(root), real(file));
+    return !relative.startsWith("..") && !path.isAbsolute(relative);
+  });
+}
+
+// Resolves a file named in a tool's arguments, relative to the worktree
+// root, refusing any outside the project's worktrees.
+function projectFile(file) {
+  const resolved = path.resolve(ROOT, String(file));
+  if (!insideProject(resolved)) {
+    throw new Error(`${file} is outside the project`);
+  }
+  return resolved;
+}
+
+// The arguments and stdin that give a `dhall` command the tool's input:
+// either a file, relative to the worktree root, or an expression, whose
+// relative imports are resolved from the root.
+function input(args) {
+  if (typeof args.file === "string") {
+    const file = projectFile(args.file);
+    checkImports(fs.readFileSync(file, "utf8"), path.dirname(file));
+    return { flags: ["--file", file], stdin: undefined };
+  }
+  if (typeof args.expression === "string") {
+    checkImports(args.expression, ROOT);
+    return { flags: [], stdin: args.expression };
+  }
+  throw new Error("give either `expression` or `file`");
+}
+
+// Follows the local imports of `source` (relative, absolute and from the
+// home directory), whose relative imports are resolved from `dir`, refusing
+// any import the permissions don't allow and any file outside the project.
+function checkImports(source, dir, seen = new Set()) {
+  for (const match of maskNonCode(source).matchAll(IMPORT)) {
+    // The masked source blanks the name in `env:"VAR"`; it's the same
+    // length as the source, so the original is sliced from that.
+    const target = source.slice(match.index, match.index + match[1].length);
+    const hash = match[2];
+    if (target.startsWith("env:") && !PERMISSIONS.env_imports) {
+      throw new Error(
+        `${target} is an environment import, which the assistant may not resolve unless ` +
+          "context_servers.dhall.settings.env_imports is true",
+      );
+    }
+    if (target.startsWith("http") && !hash && !PERMISSIONS.remote_imports) {
+      throw new Error(
+        `${target} is an unpinned remote import, which the assistant may not fetch unless ` +
+          "context_servers.dhall.settings.remote_imports is true",
+      );
+    }
+    if (target.startsWith(".") || target.startsWith("/") || target.startsWith("~/")) {
+      const file = target.startsWith("~/")
+        ? path.join(os.homedir(), target.slice(2))
+        : path.resolve(dir, target);
+      if (seen.has(file)) continue;
+      seen.add(file);
+      if (!insideProject(file)) {
+        throw new Error(`${target} is outside the project, which the assistant may not read`);
+      }
+      let imported;
+      try {
+        imported = fs.readFileSync(file, "utf8");
+      } catch {
+        continue;
+      }
+      checkImports(imported, path.dirname(file), seen);
+    }
+  }
+}
+
+// Returns `file`, relative to `dir`, as a Dhall import, quoting any path
+// segments that need it.
+function importPath(dir, file) {
+  let relative = path.relative(dir, file).split(path.sep);
+  if (relative[0] !== "..") {
+    relative = [".", ...relative];
+  }
+  const segments = relative.map((segment) =>
+    segment === "." || segment === ".." || /^[\w.-]+$/.test(segment) ? segment : `"${segment}"`,
+  );
+  return segments.join("/");
+}
+
+function isAllowed(tool) {
+  return !Array.isArray(PERMISSIONS.tools) || PERMISSIONS.tools.includes(tool.name);
+}
+
+const INPUT_SCHEMA = {
+  type: "object",
+  properties: {
+    expression: { type: "string", description: "A Dhall expression" },
+    file: { type: "string", description: "A Dhall file, relative to the project root" },
+  },
+};
+
+// Scores how well `text` matches `query`: highest for an exact match, then
+// a prefix, a substring, and finally the query's characters in order.
+function matchScore(query, text) {
+  const q = query.toLowerCase();
+  const t = text.toLowerCase();
+  if (t === q) return 100;
+  if (t.startsWith(q) || t.split(/[/.]/).pop().startsWith(q)) return 80;
+  if (t.includes(q)) return 60;
+  let ix = 0;
+  let gaps = 0;
+  for (const c of t) {
+    if (ix < q.length && c === q[ix]) {
+      ix++;
+    } else if (ix > 0 && ix < q.length) {
+      gaps++;
+    }
+  }
+  return ix === q.length ? Math.max(40 - gaps, 10) : 0;
+}
+
+function search(query, limit) {
+  const words = query.toLowerCase().split(/\s+/).filter(Boolean);
+  const results = [];
+  for (const [source, entries] of Object.entries(INDEX)) {
+    for (const entry of entries) {
+      let score = matchScore(query, entry.name);
+      const text = `${entry.signature || ""} ${entry.description || ""}`.toLowerCase();
+      score += words.filter((word) => text.includes(word)).length * 5;
+      if (score > 0) {
+        results.push({ source, entry, score });
+      }
+    }
+  }
+  results.sort((a, b) => b.score - a.score || a.entry.name.localeCompare(b.entry.name));
+  return results.slice(0, limit);
+}
+
+function truncate(text, length) {
+  return text.length > length ? `${text.slice(0, length)}…` : text;
+}
+
+// Parses a type in the normal form `dhall` prints: records, unions,
+// applications such as `List Text`, and names. Each node keeps its span of
+// `text`, so that its source can be reused when printing values.
+function parseType(text) {
+  const tokens = [...text.matchAll(/`[^`]*`|->|[{}<>(),:|]|[^\s{}<>(),:|`]+/g)].map((match) => ({
+    text: match[0],
+    start: match.index,
+    end: match.index + match[0].length,
+  }));
+  let ix = 0;
+  const peek = () => tokens[ix] && tokens[ix].text;
+  const expect = (token) => {
+    if (peek() !== token) {
+      throw new Error(`expected ${token} in type at ${tokens[ix] ? tokens[ix].start : "end"}`);
+    }
+    return tokens[ix++];
+  };
+
+  function atom() {
+    const token = tokens[ix];
+    if (!token) throw new Error("unexpected end of type");
+    if (token.text === "{" || token.text === "<") {
+      const union = token.text === "<";
+      const close = union ? ">" : "}";
+      ix++;
+      const fields = [];
+      while (peek() !== close) {
+        if (peek() === "," || peek() === "|") {
+          ix++;
+          continue;
+        }
+        const name = tokens[ix++].text;
+        let type = null;
+        if (peek() === ":") {
+          ix++;
+          type = expression();
+        }
+        fields.push({ name, type });
+      }
+      const end = expect(close).end;
+      return { kind: union ? "union" : "record", fields, start: token.start, end };
+    }
+    if (token.text === "(") {
+      ix++;
+      const inner = expression();
+      expect(")");
+      return inner;
+    }
+    if ([")", "}", ">", ",", "|", ":", "->"].includes(token.text)) {
+      throw new Error(`unexpected ${token.text} in type`);
+    }
+    ix++;
+    return { kind: "name", name: token.text, start: token.start, end: token.end };
+  }
+
+  function expression() {
+    if (peek() === "forall" || peek() === "∀") {
+      throw new Error("functions have no skeleton");
+    }
+    let node = atom();
+    while (peek() && ![")", "}", ">", ",", "|", ":", "->"].includes(peek())) {
+      const arg = atom();
+      node = { kind: "app", fn: node, arg, start: node.start, end: arg.end };
+    }
+    if (peek() === "->") {
+      throw new Error("functions have no skeleton");
+    }
+    return node;
+  }
+
+  const type = expression();
+  if (ix < tokens.length) {
+    throw new Error(`unexpected ${tokens[ix].text} in type`);
+  }
+  return type;
+}
+
+const ZERO_VALUES = {
+  Bool: "False",
+  Double: "0.0",
+  Integer: "+0",
+  Natural: "0",
+  Text: '""',
+};
+
+// Returns the simplest value of `type`, whose source is `text`: empty
+// lists, `None`, the first alternative of unions and so on.
+function zeroValue(type, text) {
+  const source = (node) => text.slice(node.start, node.end);
+  switch (type.kind) {
+    case "name":
+      if (type.name in ZERO_VALUES) return ZERO_VALUES[type.name];
+      throw new Error(`no skeleton for a value of type ${type.name}`);
+    case "record":
+      if (type.fields.length === 0) return "{=}";
+      return `{ ${type.fields.map((field) => `${field.name} = ${zeroValue(field.type, text)}`).join(", ")} }`;
+    case "union": {
+      const [first] = type.fields;
+      if (!first) throw new Error("the empty union has no values");
+      const alternative = `${source(type)}.${first.name}`;
+      return first.type ? `(${alternative} ${zeroValue(first.type, text)})` : alternative;
+    }
+    case "app":
+      if (type.fn.kind === "name" && type.fn.name === "List") return `([] : List (${source(type.arg)}))`;
+      if (type.fn.kind === "name" && type.fn.name === "Optional") return `(None (${source(type.arg)}))`;
+      throw new Error(`no skeleton for a value of type ${source(type)}`);
+  }
+}
+
+// Builds a record skeleton for `expression`, which is either a schema (a
+// record of `Type` and `default`, including `X.Type` for a schema `X`) or a
+// plain type. Schemas fill in only the fields without defaults.
+function skeleton(expression) {
+  const candidates = [expression];
+  if (/\.Type\s*$/.test(expression)) {
+    candidates.unshift(expression.replace(/\.Type\s*$/, ""));
+  }
+  for (const schema of candidates) {
+    let typeText;
+    let defaults;
+    try {
+      typeText = run("dhall", ["--ascii"], `(${schema}).Type`);
+      defaults = parseType(run("dhall", ["--ascii", "type"], `(${schema}).default`));
+    } catch {
+      continue;
+    }
+    const type = parseType(typeText);
+    if (type.kind !== "record" || defaults.kind !== "record") continue;
+    const defaulted = new Set(defaults.fields.map((field) => field.name));
+    const required = type.fields.filter((field) => !defaulted.has(field.name));
+    const fields = required.map((field) => `${field.name} = ${zeroValue(field.type, typeText)}`);
+    return `(${schema})::{ ${fields.join(", ")} }`;
+  }
+  const typeText = run("dhall", ["--ascii"], expression);
+  return `${zeroValue(parseType(typeText), typeText)} : ${expression}`;
+}
+
+// Splits the code of `source` into tokens with their offsets, telling
+// brackets apart from the other symbols.
+function codeTokens(source) {
+  const code = maskNonCode(source);
+  return [...code.matchAll(/->|===|==|::|[()[\]{}<>]|=|:|[^\s()[\]{}<>=:]+/g)].map((match) => ({
+    text: match[0],
+    start: match.index,
+    end: match.index + match[0].length,
+    open: "([{<".includes(match[0]),
+    close: ")]}>".includes(match[0]),
+  }));
+}
+
+// Returns the index of the token ending the value of a binding that starts
+// at `start`: the next `let` or `in` of the chain, skipping nested ones.
+function endOfBindingValue(tokens, start) {
+  let depth = 0;
+  let nestedLets = 0;
+  let afterIn = false;
+  for (let ix = start; ix < tokens.length; ix++) {
+    const token = tokens[ix];
+    if (token.open) {
+      depth++;
+    } else if (token.close) {
+      depth = Math.max(depth - 1, 0);
+    } else if (depth === 0 && token.text === "let") {
+      if (nestedLets === 0 && !afterIn && ix > start) return ix;
+      if (nestedLets === 0 || afterIn) nestedLets++;
+    } else if (depth === 0 && token.text === "in") {
+      if (nestedLets === 0) return ix;
+      nestedLets--;
+      afterIn = true;
+      continue;
+    }
+    afterIn = false;
+  }
+  return tokens.length;
+}
+
+// Parses the top-level `let` chain of `source` into its bindings, with the
+// offsets of each binding and its value, and the offset of the body.
+function letChain(source) {
+  const tokens = codeTokens(source);
+  const bindings = [];
+  let ix = 0;
+  while (ix < tokens.length && tokens[ix].text === "let" && ix + 1 < tokens.length) {
+    const start = tokens[ix].start;
+    const name = tokens[ix + 1].text.replace(/`/g, "");
+    ix += 2;
+    if (tokens[ix] && tokens[ix].text === ":") {
+      let depth = 0;
+      while (ix < tokens.length && !(depth === 0 && tokens[ix].text === "=")) {
+        if (tokens[ix].open) depth++;
+        if (tokens[ix].close) depth--;
+        ix++;
+      }
+    }
+    if (!tokens[ix] || tokens[ix].text !== "=") break;
+    const end = endOfBindingValue(tokens, ix + 1);
+    const last = tokens[end - 1];
+    bindings.push({
+      name,
+      start,
+      end: last ? last.end : source.length,
+      value: [tokens[ix + 1] ? tokens[ix + 1].start : source.length, last ? last.end : source.length],
+    });
+    ix = end;
+    if (tokens[ix] && tokens[ix].text === "in") ix++;
+  }
+  return { bindings, body: tokens[ix] ? tokens[ix].start : source.length };
+}
+
+// Turns each `assert` in the file's top-level `let` chain (and its body)
+// into a standalone program with the non-assertion bindings before it, so
+// that one failure doesn't mask the others, as `/dhall-test` does.
+function assertions(source) {
+  const chain = letChain(source);
+  const isAssert = (text) => /^\s*assert\b/.test(text);
+  const result = [];
+  let prelude = "";
+  for (const binding of chain.bindings) {
+    const value = source.slice(...binding.value);
+    if (isAssert(value)) {
+      result.push({ name: binding.name, program: `${prelude}in ${value}\n` });
+    } else {
+      prelude += `${source.slice(binding.start, binding.end)}\n`;
+    }
+  }
+  const body = source.slice(chain.body).trim();
+  if (isAssert(body)) {
+    const program = chain.bindings.length === 0 ? `${body}\n` : `${prelude}in ${body}\n`;
+    result.push({ name: "<body>", program });
+  }
+  return result;
+}
+
+// Runs the assertions of each file (by default, every `*Test*.dhall` file),
+// returning whether each passed and, if not, its error.
+function runTests(files) {
+  const results = [];
+  for (const file of files) {
+    const absolute = projectFile(file);
+    const source = fs.readFileSync(absolute, "utf8");
+    const dir = path.dirname(absolute);
+    checkImports(source, dir);
+    const outcomes = assertions(source).map(({ name, program }) => {
+      try {
+        run("dhall", ["type"], program, dir);
+        return { name, passed: true };
+      } catch (error) {
+        return { name, passed: false, error: error.message };
+      }
+    });
+    results.push({ file, assertions: outcomes });
+  }
+  const all = results.flatMap((result) => result.assertions);
+  return {
+    files: results,
+    passed: all.filter((outcome) => outcome.passed).length,
+    failed: all.filter((outcome) => !outcome.passed).length,
+  };
+}
+
+const TOOLS = [
+  {
+    name: "evaluate",
+    description: "Evaluate a Dhall expression or file to its normal form.",
+    inputSchema: INPUT_SCHEMA,
+    run(args) {
+      const { flags, stdin } = input(args);
+      return run("dhall", flags, stdin);
+    },
+  },
+  {
+    name: "test",
+    description:
+      "Run the `assert`s in Dhall test files, as `/dhall-test` does, returning JSON " +
+      "with whether each assertion passed and, if not, its error.",
+    inputSchema: {
+      type: "object",
+      properties: {
+        files: {
+          type: "array",
+          items: { type: "string" },
+          description: "Files relative to the project root (default: every *Test*.dhall file)",
+        },
+      },
+    },
+    run(args) {
+      const files = Array.isArray(args.files)
+        ? args.files
+        : dhallFiles(ROOT).filter((file) => path.basename(file).includes("Test"));
+      if (files.length === 0) {
+        throw new Error("no *Test*.dhall files found in the project");
+      }
+      return JSON.stringify(runTests(files), null, 2);
+    },
+  },
+  {
+    name: "type_of",
+    description: "Infer the type of a Dhall expression or file, or report its type error.",
+    inputSchema: INPUT_SCHEMA,
+    run(args) {
+      const { flags, stdin } = input(args);
+      return run("dhall", ["type", ...flags], stdin);
+    },
+  },
+  {
+    name: "resolve_imports",
+    description:
+      "Resolve the imports of a Dhall expression or file. With `dependencies`, list " +
+      "every file and URL it depends on instead.",
+    inputSchema: {
+      ...INPUT_SCHEMA,
+      properties: {
+        ...INPUT_SCHEMA.properties,
+        dependencies: { type: "boolean", description: "List the transitive dependencies" },
+      },
+    },
+    run(args) {
+      const { flags, stdin } = input(args);
+      const list = args.dependencies ? ["--transitive-dependencies"] : [];
+      return run("dhall", ["resolve", ...list, ...flags], stdin);
+    },
+  },
+  {
+    name: "diff",
+    description:
+      "Show the semantic difference between two Dhall files, or between a file and " +
+      "its contents at a git revision, as `dhall diff` does.",
+    inputSchema: {
+      type: "object",
+      properties: {
+        file: { type: "string", description: "A Dhall file, relative to the project root" },
+        other: { type: "string", description: "Another Dhall file to compare it to" },
+        revision: { type: "string", description: "A git revision to compare it to, e.g. HEAD~1" },
+      },
+      required: ["file"],
+    },
+    run(args) {
+      const file = projectFile(args.file);
+      const dir = path.dirname(file);
+      checkImports(fs.readFileSync(file, "utf8"), dir);
+      let before;
+      if (typeof args.other === "string") {
+        const other = projectFile(args.other);
+        checkImports(fs.readFileSync(other, "utf8"), path.dirname(other));
+        before = importPath(dir, other);
+      } else if (typeof args.revision === "string") {
+        // A revision starting with `-` would be taken as an option.
+        if (args.revision.startsWith("-")) {
+          throw new Error(`invalid revision: ${args.revision}`);
+        }
+        const source = run(
+          "git",
+          ["show", "--end-of-options", `${args.revision}:./${path.basename(file)}`],
+          undefined,
+          dir,
+        );
+        checkImports(source, dir);
+        before = `(${source}\n)`;
+      } else {
+        throw new Error("give either `other` or `revision`");
+      }
+      const diff = run("dhall", ["diff", before, importPath(dir, file)], undefined, dir);
+      return diff.trim() || "The expressions are the same.";
+    },
+  },
+  {
+    name: "render",
+    description:
+      "Render a Dhall expression or file as JSON or YAML, as dhall-to-json and " +
+      "dhall-to-yaml would, or report why it can't be rendered.",
+    inputSchema: {
+      ...INPUT_SCHEMA,
+      properties: {
+        ...INPUT_SCHEMA.properties,
+        format: { type: "string", enum: ["json", "yaml"], description: "Defaults to json" },
+      },
+    },
+    run(args) {
+      const { flags, stdin } = input(args);
+      switch (args.format || "json") {
+        case "json":
+          return run("dhall-to-json", flags, stdin);
+        case "yaml":
+          return run("dhall-to-yaml", flags, stdin);
+        default:
+          throw new Error(`unknown format: ${args.format}`);
+      }
+    },
+  },
+  {
+    name: "skeleton",
+    description:
+      "Generate a type-correct record skeleton for a Dhall type or schema, e.g. " +
+      "`(./k8s.dhall).Deployment`. For schemas, only the fields without defaults " +
+      "are filled in, with placeholder values to replace.",
+    inputSchema: {
+      type: "object",
+      properties: {
+        type: { type: "string", description: "A Dhall type or schema expression" },
+      },
+      required: ["type"],
+    },
+    run(args) {
+      const expression = String(args.type || "");
+      checkImports(expression, ROOT);
+      const value = skeleton(expression);
+      // Check the skeleton before handing it over.
+      run("dhall", ["type"], value);
+      return run("dhall", ["format"], value);
+    },
+  },
+  {
+    name: "search",
+    description:
+      "Fuzzy search the Dhall builtins, the Prelude and indexed dhall-kubernetes " +
+      "versions by name, returning each match's type and documentation.",
+    inputSchema: {
+      type: "object",
+      properties: {
+        query: { type: "string", description: "A name or part of one, e.g. `List/map`" },
+        limit: { type: "number", description: "The most results to return (default 10)" },
+      },
+      required: ["query"],
+    },
+    run(args) {
+      const results = search(String(args.query || ""), args.limit || 10);
+      if (results.length === 0) {
+        return `Nothing matches ${JSON.stringify(args.query)}.`;
+      }
+      return results
+        .map(({ source, entry }) => {
+          let text = `${entry.name}`;
+          if (entry.signature) {
+            text += ` : ${truncate(entry.signature, 400)}`;
+          }
+          text += `\n(${source})`;
+          if (entry.description) {
+            text += `\n${truncate(entry.description.split("\n\n")[0], 600)}`;
+          }
+          return text;
+        })
+        .join("\n\n");
+    },
+  },
+];
+
+// Returns `source` with its comments and the contents of its text literals
+// blanked out, so that only code is left to scan. Offsets and line breaks are
+// kept, as are the quotes, so that a literal is still a token.
+function maskNonCode(source) {
+  const blank = (text) => text.replace(/[^\n]/g, " ");
+  let code = "";
+  let ix = 0;
+  while (ix < source.length) {
+    const start = ix;
+    if (source.startsWith("--", ix)) {
+      const end = source.indexOf("\n", ix);
+      ix = end === -1 ? source.length : end;
+    } else if (source.startsWith("{-", ix)) {
+      let depth = 0;
+      do {
+        if (source.startsWith("{-", ix)) {
+          depth++;
+          ix += 2;
+        } else if (source.startsWith("-}", ix)) {
+          depth--;
+          ix += 2;
+        } else {
+          ix++;
+        }
+      } while (depth > 0 && ix < source.length);
+    } else if (source[ix] === '"') {
+      ix++;
+      while (ix < source.length && source[ix] !== '"') {
+        ix += source[ix] === "\\" ? 2 : 1;
+      }
+      code += `"${blank(source.slice(start + 1, ix))}"`;
+      ix++;
+      continue;
+    } else if (source.startsWith("''", ix)) {
+      ix += 2;
+      while (ix < source.length && !(source.startsWith("''", ix) && source[ix + 2] !== "'")) {
+        ix += source.startsWith("'''", ix) ? 3 : 1;
+      }
+      code += `''${blank(source.slice(start + 2, ix))}''`;
+      ix += 2;
+      continue;
+    } else {
+      code += source[ix++];
+      continue;
+    }
+    code += blank(source.slice(start, ix));
+  }
+  return code;
+}
+
+const IMPORT =
+  /(?<![\w/.-])(https?:\/\/[^\s)]+|\.{1,2}\/[^\s)]+|~\/[^\s)]+|\/(?![/\\])[^\s)]+|env:(?:[A-Za-z_]\w*|"[^"]*"))(?:\s+(sha256:[0-9a-f]{64}))?/g;
+
+// Lists the `.dhall` files under `root`, relative to it, skipping hidden
+// directories.
+function dhallFiles(root, dir = "") {
+  let files = [];
+  let entries;
+  try {
+    entries = fs.readdirSync(path.join(root, dir), { withFileTypes: true });
+  } catch {
+    return files;
+  }
+  for (const entry of entries) {
+    if (entry.name.startsWith(".")) continue;
+    const file = path.posix.join(dir, entry.name);
+    if (entry.isDirectory()) {
+      files = files.concat(dhallFiles(root, file));
+    } else if (entry.name.endsWith(".dhall")) {
+      files.push(file);
+    }
+  }
+  return files.sort();
+}
+
+function fileSize(file) {
+  try {
+    return fs.statSync(file).size;
+  } catch {
+    return null;
+  }
+}
+
+function cacheDir() {
+  const base = process.env.XDG_CACHE_HOME || path.join(os.homedir(), ".cache");
+  return path.join(base, "dhall");
+}
+
+// Builds the import graph of every Dhall file in the project: its files
+// with their sizes, the URLs and environment variables they import, and an
+// edge for each import with its integrity check. Remote imports are sized
+// by their cache entry, when they are pinned and cached.
+function importGraph() {
+  const roots = ROOTS.length ? ROOTS : [ROOT];
+  // With several roots, files are named by their absolute paths.
+  const nameOf = (root, file) => (roots.length > 1 ? path.join(root, file) : file);
+  const files = {};
+  const urls = {};
+  const env = [];
+  const edges = [];
+  for (const root of roots) {
+    for (const file of dhallFiles(root)) {
+      const absolute = path.join(root, file);
+      files[nameOf(root, file)] = { size: fileSize(absolute) };
+      const source = fs.readFileSync(absolute, "utf8");
+      for (const match of maskNonCode(source).matchAll(IMPORT)) {
+        const target = source.slice(match.index, match.index + match[1].length);
+        const hash = match[2];
+        let to = target;
+        if (target.startsWith("http")) {
+          const url = (urls[target] = urls[target] || { hashes: [], size: null });
+          if (hash && !url.hashes.includes(hash)) {
+            url.hashes.push(hash);
+            url.size = fileSize(path.join(cacheDir(), `1220${hash.slice("sha256:".length)}`));
+          }
+        } else if (target.startsWith("env:")) {
+          if (!env.includes(target)) env.push(target);
+        } else if (target.startsWith(".")) {
+          to = nameOf(root, path.posix.normalize(path.posix.join(path.posix.dirname(file), target)));
+        }
+        edges.push({ from: nameOf(root, file), to, hash: hash || null });
+      }
+    }
+  }
+  return { roots, files, urls, env, edges };
+}
+
+const RESOURCES = [
+  {
+    uri: "dhall://import-graph",
+    name: "Dhall import graph",
+    description:
+      "Every Dhall file in the project with its size, the URLs and environment " +
+      "variables it imports, and each import with its sha256 integrity check.",
+    mimeType: "application/json",
+    read: () => JSON.stringify(importGraph(), null, 2),
+  },
+];
+
+class RpcError extends Error {
+  constructor(code, message) {
+    super(message);
+    this.code = code;
+  }
+}
+
+const METHODS = {
+  initialize: (params) => ({
+    protocolVersion: params.protocolVersion || "2024-11-05",
+    capabilities: { tools: {}, resources: {} },
+    serverInfo: { name: "dhall", version: "0.1.0" },
+    ...(process.env.DHALL_MCP_INSTRUCTIONS && { instructions: process.env.DHALL_MCP_INSTRUCTIONS }),
+  }),
+  ping: () => ({}),
+  "tools/list": () => ({
+    tools: TOOLS.filter(isAllowed).map(({ name, description, inputSchema }) => ({
+      name,
+      description,
+      inputSchema,
+    })),
+  }),
+  "resources/list": () => ({
+    resources: RESOURCES.map(({ uri, name, description, mimeType }) => ({
+      uri,
+      name,
+      description,
+      mimeType,
+    })),
+  }),
+  "resources/read": (params) => {
+    const resource = RESOURCES.find((resource) => resource.uri === params.uri);
+    if (!resource) {
+      throw new RpcError(-32002, `unknown resource: ${params.uri}`);
+    }
+    return { contents: [{ uri: resource.uri, mimeType: resource.mimeType, text: resource.read() }] };
+  },
+  "tools/call": (params) => {
+    const tool = TOOLS.find((tool) => tool.name === params.name);
+    if (!tool || !isAllowed(tool)) {
+      throw new RpcError(-32602, `unknown tool: ${params.name}`);
+    }
+    try {
+      return { content: [{ type: "text", text: tool.run(params.arguments || {}) }] };
+    } catch (error) {
+      return { content: [{ type: "text", text: error.message }], isError: true };
+    }
+  },
+};
+
+function send(message) {
+  process.stdout.write(JSON.stringify({ jsonrpc: "2.0", ...message }) + "\n");
+}
+
+readline.createInterface({ input: process.stdin }).on("line", (line) => {
+  if (!line.trim()) {
+    return;
+  }
+  let request;
+  try {
+    request = JSON.parse(line);
+  } catch (error) {
+    send({ id: null, error: { code: -32700, message: error.message } });
+    return;
+  }
+  // Notifications, such as `notifications/initialized`, need no response.
+  if (request.id === undefined) {
+    return;
+  }
+  const method = METHODS[request.method];
+  if (!method) {
+    send({ id: request.id, error: { code: -32601, message: `unknown method: ${request.method}` } });
+    return;
+  }
+  try {
+    send({ id: request.id, result: method(request.params || {}) });
+  } catch (error) {
+    send({ id: request.id, error: { code: error.code || -32603, message: error.message } });
+  }
+});
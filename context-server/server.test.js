@@ -0,0 +1,98 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+
+// Tests for the permission checks, run with `node --test context-server/`.
+// Each call is refused before `dhall` would run, so it needn't be installed.
+
+"use strict";
+
+const assert = require("assert");
+const { spawnSync } = require("child_process");
+const fs = require("fs");
+const os = require("os");
+const path = require("path");
+const test = require("node:test");
+
+const SERVER = path.join(__dirname, "server.js");
+
+// Calls `tool` on a server for a project of `files`, returning the text of
+// its result and whether it is an error.
+function call(files, tool, args, permissions = {}) {
+  const base = fs.mkdtempSync(path.join(os.tmpdir(), "dhall-mcp-"));
+  const root = path.join(base, "project");
+  for (const [file, source] of Object.entries(files)) {
+    fs.mkdirSync(path.dirname(path.join(base, file)), { recursive: true });
+    fs.writeFileSync(path.join(base, file), source);
+  }
+  fs.mkdirSync(root, { recursive: true });
+  const request = { jsonrpc: "2.0", id: 1, method: "tools/call", params: { name: tool, arguments: args } };
+  const result = spawnSync("node", [SERVER], {
+    input: JSON.stringify(request) + "\n",
+    encoding: "utf8",
+    env: {
+      ...process.env,
+      DHALL_MCP_ROOTS: JSON.stringify([root]),
+      DHALL_MCP_PERMISSIONS: JSON.stringify(permissions),
+    },
+  });
+  fs.rmSync(base, { recursive: true, force: true });
+  const response = JSON.parse(result.stdout);
+  return { text: response.result.content[0].text, isError: response.result.isError };
+}
+
+test("refuses tool files outside the project", () => {
+  const { text, isError } = call({ "outside.dhall": "1" }, "type_of", { file: "../outside.dhall" });
+  assert.ok(isError);
+  assert.match(text, /outside the project/);
+});
+
+test("refuses imports of files in the home directory", () => {
+  const { text, isError } = call({}, "type_of", { expression: "~/.ssh/id_rsa as Text" });
+  assert.ok(isError);
+  assert.match(text, /~\/\.ssh\/id_rsa is outside the project/);
+});
+
+test("refuses absolute imports outside the project", () => {
+  const { text, isError } = call({}, "type_of", { expression: "/etc/passwd as Text" });
+  assert.ok(isError);
+  assert.match(text, /\/etc\/passwd is outside the project/);
+});
+
+test("refuses relative imports that leave the project, however deep", () => {
+  const { text, isError } = call(
+    {
+      "project/a.dhall": "./b.dhall",
+      "project/b.dhall": "../outside.dhall",
+      "outside.dhall": "1",
+    },
+    "type_of",
+    { file: "a.dhall" },
+  );
+  assert.ok(isError);
+  assert.match(text, /\.\.\/outside\.dhall is outside the project/);
+});
+
+test("refuses quoted environment imports", () => {
+  const { text, isError } = call({}, "type_of", { expression: 'env:"HOME" as Text' });
+  assert.ok(isError);
+  assert.match(text, /env:"HOME" is an environment import/);
+});
+
+test("allows environment imports when permitted", () => {
+  const { text } = call({}, "type_of", { expression: 'env:"HOME" as Text' }, { env_imports: true });
+  assert.doesNotMatch(text, /environment import/);
+});
@@ -0,0 +1,127 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+// Runs the language server given as arguments, filtering what it sends to
+// Zed according to `DHALL_LSP_FILTER`, a JSON object of:
+//
+// - `formatter_only`: drop the server's diagnostics and cut its capabilities
+//   down to formatting, so that Zed doesn't ask it for completions, hovers
+//   and the like.
+// - `max_diagnostic_length`: truncate diagnostic messages to this many
+//   characters.
+// - `explain`: when false, drop the long explanation from type errors.
+//
+// Zed's messages are passed through untouched.
+
+"use strict";
+
+const { spawn } = require("child_process");
+
+const OPTIONS = JSON.parse(process.env.DHALL_LSP_FILTER || "{}");
+
+// The capabilities a formatter needs: the documents' contents, and
+// formatting them.
+const KEPT_CAPABILITIES = [
+  "textDocumentSync",
+  "documentFormattingProvider",
+  "documentRangeFormattingProvider",
+  "positionEncoding",
+];
+
+const [program, ...args] = process.argv.slice(2);
+const server = spawn(program, args, { stdio: ["pipe", "pipe", "inherit"] });
+server.on("exit", (code, signal) => process.exit(signal ? 1 : code));
+server.on("error", (error) => {
+  process.stderr.write(`failed to run ${program}: ${error.message}\n`);
+  process.exit(1);
+});
+process.stdin.pipe(server.stdin);
+
+// Shortens a diagnostic's message as the options ask. `dhall` separates the
+// explanation of a type error from the error itself with a line of `─`s.
+function shorten(text) {
+  if (OPTIONS.explain === false) {
+    const explanation = text.search(/\n\s*(─+\s*\n\s*)?Explanation:/);
+    if (explanation !== -1) {
+      text = text.slice(0, explanation).trimEnd();
+    }
+  }
+  const max = OPTIONS.max_diagnostic_length;
+  if (Number.isInteger(max) && max > 0 && text.length > max) {
+    text = `${text.slice(0, max)}… (${text.length - max} more characters)`;
+  }
+  return text;
+}
+
+function filter(message) {
+  if (message.method === "textDocument/publishDiagnostics") {
+    if (OPTIONS.formatter_only) {
+      return null;
+    }
+    for (const diagnostic of message.params.diagnostics || []) {
+      diagnostic.message = shorten(diagnostic.message);
+    }
+    return message;
+  }
+  if (OPTIONS.formatter_only && message.result && message.result.capabilities) {
+    const capabilities = {};
+    for (const name of KEPT_CAPABILITIES) {
+      if (name in message.result.capabilities) {
+        capabilities[name] = message.result.capabilities[name];
+      }
+    }
+    message.result.capabilities = capabilities;
+  }
+  return message;
+}
+
+// Splits the server's output into messages, each a `Content-Length` header
+// and a JSON body, and writes out the ones that survive `filter`.
+let buffer = Buffer.alloc(0);
+server.stdout.on("data", (chunk) => {
+  buffer = Buffer.concat([buffer, chunk]);
+  for (;;) {
+    const headerEnd = buffer.indexOf("\r\n\r\n");
+    if (headerEnd === -1) return;
+    const length = /content-length:\s*(\d+)/i.exec(buffer.subarray(0, headerEnd).toString());
+    if (!length) {
+      // Not a message we understand, so pass the rest through as it is.
+      process.stdout.write(buffer);
+      buffer = Buffer.alloc(0);
+      return;
+    }
+    const start = headerEnd + 4;
+    const end = start + Number(length[1]);
+    if (buffer.length < end) return;
+    const body = buffer.subarray(start, end);
+    buffer = buffer.subarray(end);
+
+    let message;
+    try {
+      message = filter(JSON.parse(body.toString("utf8")));
+    } catch {
+      message = undefined;
+    }
+    if (message === undefined) {
+      process.stdout.write(`Content-Length: ${body.length}\r\n\r\n`);
+      process.stdout.write(body);
+    } else if (message !== null) {
+      const json = Buffer.from(JSON.stringify(message), "utf8");
+      process.stdout.write(`Content-Length: ${json.length}\r\n\r\n`);
+      process.stdout.write(json);
+    }
+  }
+});
@@ -0,0 +1,387 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use zed_extension_api::{self as zed, Result, SlashCommandOutput, SlashCommandOutputSection};
+
+use crate::imports::{self, Import};
+use crate::syntax::{self, TokenKind};
+use crate::{cli, prelude, worktree};
+
+pub const FREEZE_ALL_SLASH_COMMAND: &str = "dhall-freeze-all";
+pub const HASH_DRIFT_SLASH_COMMAND: &str = "dhall-hash-drift";
+pub const STALE_SLASH_COMMAND: &str = "dhall-stale-hashes";
+pub const UNPINNED_SLASH_COMMAND: &str = "dhall-unpinned";
+
+/// An import found while scanning the worktree.
+pub struct Located {
+    pub file: String,
+    pub line: usize,
+    pub import: Import,
+    /// The text of the import's `sha256:…` integrity check, if any.
+    pub hash: Option<String>,
+}
+
+/// Returns every import in the worktree at `root` for which `predicate`
+/// holds, in file order.
+pub fn find_imports(root: &Path, predicate: impl Fn(&Import) -> bool) -> Vec<Located> {
+    let mut found = Vec::new();
+    for file in worktree::dhall_files(root) {
+        let Ok(source) = fs::read_to_string(root.join(&file)) else {
+            continue;
+        };
+        for import in imports::imports(&source) {
+            if predicate(&import) {
+                found.push(Located {
+                    file: file.clone(),
+                    line: source[..import.span.start].matches('\n').count() + 1,
+                    hash: import.hash.clone().map(|hash| source[hash].to_string()),
+                    import,
+                });
+            }
+        }
+    }
+    found
+}
+
+fn is_unpinned(import: &Import) -> bool {
+    let remote = import.target.starts_with("https://") || import.target.starts_with("http://");
+    remote && import.hash.is_none()
+}
+
+/// Lists remote imports without an integrity check, and with `freeze`, pins
+/// them with `dhall freeze`.
+pub fn run_unpinned_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let freeze = match args.first().map(String::as_str) {
+        Some("freeze") => true,
+        Some(_) => return Err("usage: /dhall-unpinned [freeze]".to_string()),
+        None => false,
+    };
+    let root = PathBuf::from(worktree.root_path());
+    let unpinned = find_imports(&root, is_unpinned);
+
+    let mut text = String::new();
+    if unpinned.is_empty() {
+        text.push_str("Every remote import has a sha256 integrity check.\n");
+    } else if freeze {
+        let dhall = cli::which(worktree, "dhall")?;
+        let mut files: Vec<&str> = unpinned.iter().map(|found| found.file.as_str()).collect();
+        files.dedup();
+        for file in files {
+            let output = cli::run(&dhall, &["freeze", "--inplace", file], &root, None)?;
+            if output.success {
+                text.push_str(&format!("- froze {file}\n"));
+            } else {
+                text.push_str(&format!(
+                    "- failed to freeze {file}: {}\n",
+                    output.stderr.trim()
+                ));
+            }
+        }
+    } else {
+        text.push_str(&format!(
+            "{} remote imports have no sha256 integrity check, so their contents can change \
+             without notice:\n\n",
+            unpinned.len()
+        ));
+        for found in &unpinned {
+            text.push_str(&format!(
+                "- {}:{}: {}\n",
+                found.file, found.line, found.import.target
+            ));
+        }
+        text.push_str("\nRun `/dhall-unpinned freeze` (or the `dhall freeze` task) to pin them.\n");
+    }
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: "Unpinned imports".to_string(),
+        }],
+        text,
+    })
+}
+
+/// A remote import pinned to different hashes in different places.
+pub struct Drift {
+    pub url: String,
+    pub imports: Vec<(Located, String)>,
+}
+
+pub fn hash_drift(root: &Path) -> Vec<Drift> {
+    let mut drift: Vec<Drift> = Vec::new();
+    let pinned = find_imports(root, |import| {
+        import.hash.is_some() && import.target.contains("://")
+    });
+    for found in pinned {
+        let Some(hash) = found.hash.clone() else {
+            continue;
+        };
+        match drift
+            .iter_mut()
+            .find(|drift| drift.url == found.import.target)
+        {
+            Some(drift) => drift.imports.push((found, hash)),
+            None => drift.push(Drift {
+                url: found.import.target.clone(),
+                imports: vec![(found, hash)],
+            }),
+        }
+    }
+    drift.retain(|drift| {
+        drift
+            .imports
+            .iter()
+            .any(|(_, hash)| *hash != drift.imports[0].1)
+    });
+    drift
+}
+
+/// Re-pins every import of each drifting URL to its current hash. Every
+/// hash is computed before any file is rewritten.
+fn fix_drift(dhall: &str, root: &Path, drift: &[Drift]) -> Result<Vec<(String, String)>> {
+    let mut fixed = Vec::new();
+    for drift in drift {
+        let hash = prelude::semantic_hash(dhall, root, &drift.url)?;
+        fixed.push((drift.url.clone(), hash));
+    }
+
+    let mut files: Vec<&str> = drift
+        .iter()
+        .flat_map(|drift| drift.imports.iter().map(|(found, _)| found.file.as_str()))
+        .collect();
+    files.sort_unstable();
+    files.dedup();
+    let mut rewritten = Vec::new();
+    for file in files {
+        let source = fs::read_to_string(root.join(file))
+            .map_err(|e| format!("failed to read {file}: {e}"))?;
+        let mut output = String::with_capacity(source.len());
+        let mut last = 0;
+        for import in imports::imports(&source) {
+            let Some(span) = import.hash else {
+                continue;
+            };
+            let Some((_, hash)) = fixed.iter().find(|(url, _)| *url == import.target) else {
+                continue;
+            };
+            output.push_str(&source[last..span.start]);
+            output.push_str(hash);
+            last = span.end;
+        }
+        output.push_str(&source[last..]);
+        if output != source {
+            rewritten.push((file.to_string(), output));
+        }
+    }
+    worktree::write_files(root, &rewritten)?;
+    Ok(fixed)
+}
+
+/// Reports remote imports pinned to different hashes, and files importing
+/// different Prelude releases. With `fix`, re-pins drifting imports to the
+/// hash of their current contents.
+pub fn run_hash_drift_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let fix = match args.first().map(String::as_str) {
+        Some("fix") => true,
+        Some(_) => return Err("usage: /dhall-hash-drift [fix]".to_string()),
+        None => false,
+    };
+    let root = PathBuf::from(worktree.root_path());
+    let drift = hash_drift(&root);
+
+    let mut text = String::new();
+    if drift.is_empty() {
+        text.push_str("Each remote import is pinned to a single hash.\n");
+    } else if fix {
+        let dhall = cli::which(worktree, "dhall")?;
+        for (url, hash) in fix_drift(&dhall, &root, &drift)? {
+            text.push_str(&format!("- pinned {url} to {hash}\n"));
+        }
+    } else {
+        for drift in &drift {
+            text.push_str(&format!("{} is pinned to different hashes:\n\n", drift.url));
+            for (found, hash) in &drift.imports {
+                text.push_str(&format!("- {}:{}: {hash}\n", found.file, found.line));
+            }
+            text.push('\n');
+        }
+        text.push_str(
+            "Run `/dhall-hash-drift fix` to pin each to the hash of its current contents.\n",
+        );
+    }
+
+    let versions = prelude::project_versions(&root);
+    if versions.len() > 1 {
+        text.push_str(&format!(
+            "\nThe project imports {} Prelude releases ({}). Run `/dhall-prelude-bump {}` \
+             to use one.\n",
+            versions.len(),
+            versions.join(", "),
+            versions[0]
+        ));
+    }
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: "Hash drift".to_string(),
+        }],
+        text,
+    })
+}
+
+/// Re-fetches each pinned remote import and reports the pins that no longer
+/// match the hash of the contents served upstream.
+pub fn run_stale_slash_command(
+    _args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let root = PathBuf::from(worktree.root_path());
+    let dhall = cli::which(worktree, "dhall")?;
+    let pinned = find_imports(&root, |import| {
+        import.hash.is_some() && import.target.contains("://")
+    });
+
+    let mut pins: Vec<(&str, &str, Vec<&Located>)> = Vec::new();
+    for found in &pinned {
+        let Some(hash) = found.hash.as_deref() else {
+            continue;
+        };
+        match pins
+            .iter_mut()
+            .find(|(url, pin, _)| *url == found.import.target && *pin == hash)
+        {
+            Some((_, _, locations)) => locations.push(found),
+            None => pins.push((&found.import.target, hash, vec![found])),
+        }
+    }
+
+    let (mut current, mut stale, mut unreachable) = (0, 0, 0);
+    let mut text = String::new();
+    for (url, pin, locations) in &pins {
+        let status = match prelude::semantic_hash(&dhall, &root, url) {
+            Ok(hash) if hash == *pin => {
+                current += 1;
+                continue;
+            }
+            Ok(hash) => {
+                stale += 1;
+                format!("upstream is now {hash}")
+            }
+            Err(e) => {
+                unreachable += 1;
+                format!("could not be checked: {e}")
+            }
+        };
+        text.push_str(&format!("{url}\n  pinned to {pin}\n  {status}\n"));
+        for found in locations {
+            text.push_str(&format!("  - {}:{}\n", found.file, found.line));
+        }
+        text.push('\n');
+    }
+    text.push_str(&format!(
+        "Checked {} pinned imports: {current} current, {stale} stale, {unreachable} unreachable.\n",
+        pins.len()
+    ));
+    if stale > 0 {
+        text.push_str(
+            "Stale pins still resolve from the cache, but differ from what the URL now serves. \
+             Review the upstream change, then re-pin with `dhall freeze --all`.\n",
+        );
+    }
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: "Stale hashes".to_string(),
+        }],
+        text,
+    })
+}
+
+/// Returns the text of every `sha256:…` integrity check in `source`,
+/// including those on `missing` fallbacks.
+fn pins(source: &str) -> Vec<String> {
+    syntax::code_tokens(source)
+        .iter()
+        .filter(|token| token.kind == TokenKind::Hash)
+        .map(|token| token.text(source).to_string())
+        .collect()
+}
+
+/// Runs `dhall freeze --all --cache` over every file in the worktree and
+/// counts the pins it adds and changes.
+pub fn run_freeze_all_slash_command(
+    _args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let root = PathBuf::from(worktree.root_path());
+    let dhall = cli::which(worktree, "dhall")?;
+
+    let (mut added, mut changed, mut failed) = (0, 0, 0);
+    let mut text = String::new();
+    for file in worktree::dhall_files(&root) {
+        let before = fs::read_to_string(root.join(&file)).unwrap_or_default();
+        let output = cli::run(
+            &dhall,
+            &["freeze", "--all", "--cache", "--inplace", &file],
+            &root,
+            None,
+        )?;
+        if !output.success {
+            failed += 1;
+            text.push_str(&format!(
+                "- failed to freeze {file}: {}\n",
+                output.stderr.trim()
+            ));
+            continue;
+        }
+        let after = fs::read_to_string(root.join(&file)).unwrap_or_default();
+        let (before, after) = (pins(&before), pins(&after));
+        let new = after.iter().filter(|pin| !before.contains(pin)).count();
+        let gone = before.iter().filter(|pin| !after.contains(pin)).count();
+        // A pin that disappeared was replaced by one of the new ones.
+        let file_changed = gone.min(new);
+        let file_added = new - file_changed;
+        if file_added + file_changed > 0 {
+            text.push_str(&format!(
+                "- {file}: {file_added} newly pinned, {file_changed} updated\n"
+            ));
+        }
+        added += file_added;
+        changed += file_changed;
+    }
+    text.push_str(&format!(
+        "\nFroze the worktree: {added} imports newly pinned, {changed} updated"
+    ));
+    if failed > 0 {
+        text.push_str(&format!(", {failed} files failed"));
+    }
+    text.push_str(".\n");
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: "Freeze all".to_string(),
+        }],
+        text,
+    })
+}
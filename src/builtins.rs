@@ -0,0 +1,326 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+pub struct Builtin {
+    pub name: &'static str,
+    pub signature: &'static str,
+    pub doc: &'static str,
+}
+
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "Natural/build",
+        signature: "(∀(natural : Type) → ∀(succ : natural → natural) → ∀(zero : natural) → natural) → Natural",
+        doc: "Build a Natural number from a fold",
+    },
+    Builtin {
+        name: "Natural/fold",
+        signature: "Natural → ∀(natural : Type) → ∀(succ : natural → natural) → ∀(zero : natural) → natural",
+        doc: "Apply a function n times to a starting value",
+    },
+    Builtin {
+        name: "Natural/isZero",
+        signature: "Natural → Bool",
+        doc: "Test whether a Natural number is 0",
+    },
+    Builtin {
+        name: "Natural/even",
+        signature: "Natural → Bool",
+        doc: "Test whether a Natural number is even",
+    },
+    Builtin {
+        name: "Natural/odd",
+        signature: "Natural → Bool",
+        doc: "Test whether a Natural number is odd",
+    },
+    Builtin {
+        name: "Natural/toInteger",
+        signature: "Natural → Integer",
+        doc: "Convert a Natural number to the equivalent Integer",
+    },
+    Builtin {
+        name: "Natural/show",
+        signature: "Natural → Text",
+        doc: "Render a Natural number as Text",
+    },
+    Builtin {
+        name: "Natural/subtract",
+        signature: "Natural → Natural → Natural",
+        doc: "Subtract the first argument from the second, clamping to 0",
+    },
+    Builtin {
+        name: "Integer/toDouble",
+        signature: "Integer → Double",
+        doc: "Convert an Integer to the nearest Double",
+    },
+    Builtin {
+        name: "Integer/show",
+        signature: "Integer → Text",
+        doc: "Render an Integer as Text, including its sign",
+    },
+    Builtin {
+        name: "Integer/negate",
+        signature: "Integer → Integer",
+        doc: "Negate an Integer",
+    },
+    Builtin {
+        name: "Integer/clamp",
+        signature: "Integer → Natural",
+        doc: "Convert an Integer to a Natural number, clamping negatives to 0",
+    },
+    Builtin {
+        name: "Double/show",
+        signature: "Double → Text",
+        doc: "Render a Double as Text",
+    },
+    Builtin {
+        name: "List/build",
+        signature: "∀(a : Type) → (∀(list : Type) → ∀(cons : a → list → list) → ∀(nil : list) → list) → List a",
+        doc: "Build a List from a fold",
+    },
+    Builtin {
+        name: "List/fold",
+        signature: "∀(a : Type) → List a → ∀(list : Type) → ∀(cons : a → list → list) → ∀(nil : list) → list",
+        doc: "Fold over the elements of a List from right to left",
+    },
+    Builtin {
+        name: "List/length",
+        signature: "∀(a : Type) → List a → Natural",
+        doc: "Return the number of elements in a List",
+    },
+    Builtin {
+        name: "List/head",
+        signature: "∀(a : Type) → List a → Optional a",
+        doc: "Return the first element of a List, if any",
+    },
+    Builtin {
+        name: "List/last",
+        signature: "∀(a : Type) → List a → Optional a",
+        doc: "Return the last element of a List, if any",
+    },
+    Builtin {
+        name: "List/indexed",
+        signature: "∀(a : Type) → List a → List { index : Natural, value : a }",
+        doc: "Tag each element of a List with its index",
+    },
+    Builtin {
+        name: "List/reverse",
+        signature: "∀(a : Type) → List a → List a",
+        doc: "Reverse the order of the elements of a List",
+    },
+    Builtin {
+        name: "Text/show",
+        signature: "Text → Text",
+        doc: "Render Text as a Dhall or JSON string literal",
+    },
+    Builtin {
+        name: "Text/replace",
+        signature: "∀(needle : Text) → ∀(replacement : Text) → ∀(haystack : Text) → Text",
+        doc: "Replace every occurrence of needle in haystack",
+    },
+    Builtin {
+        name: "Date/show",
+        signature: "Date → Text",
+        doc: "Render a Date as a Dhall literal",
+    },
+    Builtin {
+        name: "Time/show",
+        signature: "Time → Text",
+        doc: "Render a Time as a Dhall literal",
+    },
+    Builtin {
+        name: "TimeZone/show",
+        signature: "TimeZone → Text",
+        doc: "Render a TimeZone as a Dhall literal",
+    },
+    Builtin {
+        name: "None",
+        signature: "∀(a : Type) → Optional a",
+        doc: "An empty Optional value of the given type",
+    },
+    Builtin {
+        name: "True",
+        signature: "Bool",
+        doc: "The Bool value true",
+    },
+    Builtin {
+        name: "False",
+        signature: "Bool",
+        doc: "The Bool value false",
+    },
+    Builtin {
+        name: "Bool",
+        signature: "Type",
+        doc: "The type of True and False",
+    },
+    Builtin {
+        name: "Natural",
+        signature: "Type",
+        doc: "Non-negative integers, e.g. 0, 1, 2",
+    },
+    Builtin {
+        name: "Integer",
+        signature: "Type",
+        doc: "Signed integers, e.g. -1, +0, +1",
+    },
+    Builtin {
+        name: "Double",
+        signature: "Type",
+        doc: "IEEE 754 double-precision floating point numbers",
+    },
+    Builtin {
+        name: "Text",
+        signature: "Type",
+        doc: "Unicode text",
+    },
+    Builtin {
+        name: "Bytes",
+        signature: "Type",
+        doc: "Binary data, e.g. 0x\"00FF\"",
+    },
+    Builtin {
+        name: "Date",
+        signature: "Type",
+        doc: "Calendar dates, e.g. 2024-01-31",
+    },
+    Builtin {
+        name: "Time",
+        signature: "Type",
+        doc: "Times of day, e.g. 12:30:00",
+    },
+    Builtin {
+        name: "TimeZone",
+        signature: "Type",
+        doc: "Time zone offsets, e.g. +05:00",
+    },
+    Builtin {
+        name: "List",
+        signature: "Type → Type",
+        doc: "Homogeneous lists of values",
+    },
+    Builtin {
+        name: "Optional",
+        signature: "Type → Type",
+        doc: "A value that may be absent",
+    },
+    Builtin {
+        name: "Type",
+        signature: "Kind",
+        doc: "The type of types",
+    },
+    Builtin {
+        name: "Kind",
+        signature: "Sort",
+        doc: "The type of Type",
+    },
+];
+
+pub struct Keyword {
+    pub name: &'static str,
+    pub syntax: &'static str,
+    pub doc: &'static str,
+}
+
+pub fn lookup_keyword(name: &str) -> Option<&'static Keyword> {
+    KEYWORDS.iter().find(|keyword| keyword.name == name)
+}
+
+pub const KEYWORDS: &[Keyword] = &[
+    Keyword {
+        name: "let",
+        syntax: "let x = e₀ in e₁",
+        doc: "Bind a name to a value within an expression",
+    },
+    Keyword {
+        name: "in",
+        syntax: "let x = e₀ in e₁",
+        doc: "Introduce the body of a let expression",
+    },
+    Keyword {
+        name: "if",
+        syntax: "if b then t else f",
+        doc: "Choose between two values of the same type",
+    },
+    Keyword {
+        name: "then",
+        syntax: "if b then t else f",
+        doc: "The value of an if expression when the condition is True",
+    },
+    Keyword {
+        name: "else",
+        syntax: "if b then t else f",
+        doc: "The value of an if expression when the condition is False",
+    },
+    Keyword {
+        name: "merge",
+        syntax: "merge handlers union",
+        doc: "Consume a union (or Optional) with a record of handlers, one per alternative",
+    },
+    Keyword {
+        name: "toMap",
+        syntax: "toMap record",
+        doc: "Convert a record into a List of { mapKey, mapValue } pairs",
+    },
+    Keyword {
+        name: "showConstructor",
+        syntax: "showConstructor union",
+        doc: "Return the name of a union alternative as Text",
+    },
+    Keyword {
+        name: "with",
+        syntax: "record with a.b.c = value",
+        doc: "Update a (possibly nested) field of a record",
+    },
+    Keyword {
+        name: "assert",
+        syntax: "assert : a ≡ b",
+        doc: "Check at type-checking time that two expressions are equivalent",
+    },
+    Keyword {
+        name: "forall",
+        syntax: "∀(x : A) → B",
+        doc: "The type of a function whose result type depends on its argument",
+    },
+    Keyword {
+        name: "using",
+        syntax: "https://… using headers",
+        doc: "Send custom HTTP headers when resolving a remote import",
+    },
+    Keyword {
+        name: "as",
+        syntax: "import as Text | Location | Bytes",
+        doc: "Import a file's raw contents or location instead of a Dhall expression",
+    },
+    Keyword {
+        name: "missing",
+        syntax: "missing",
+        doc: "An import that always fails, useful with ? and integrity checks",
+    },
+    Keyword {
+        name: "Some",
+        syntax: "Some value",
+        doc: "A present Optional value",
+    },
+    Keyword {
+        name: "Location",
+        syntax: "< Local : Text | Remote : Text | Environment : Text | Missing >",
+        doc: "The type of an import imported with `as Location`",
+    },
+];
@@ -0,0 +1,247 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use zed_extension_api::{self as zed, Result, SlashCommandOutput, SlashCommandOutputSection};
+
+use crate::{audit, cli, imports, worktree};
+
+pub const CLEAR_SLASH_COMMAND: &str = "dhall-cache-clear";
+pub const STATS_SLASH_COMMAND: &str = "dhall-cache-stats";
+pub const WARM_SLASH_COMMAND: &str = "dhall-cache-warm";
+
+/// Returns the directory `dhall` caches frozen imports in, following the
+/// XDG base directory conventions it uses.
+pub fn cache_dir(worktree: &zed::Worktree) -> Result<PathBuf> {
+    let env = worktree.shell_env();
+    let var = |name: &str| {
+        env.iter()
+            .find(|(key, value)| key == name && !value.is_empty())
+            .map(|(_, value)| PathBuf::from(value))
+    };
+    let base = match zed::current_platform() {
+        (zed::Os::Windows, _) => var("XDG_CACHE_HOME").or_else(|| var("LOCALAPPDATA")),
+        _ => var("XDG_CACHE_HOME").or_else(|| var("HOME").map(|home| home.join(".cache"))),
+    };
+    base.map(|base| base.join("dhall"))
+        .ok_or_else(|| "could not determine the Dhall cache directory".to_string())
+}
+
+/// Returns the `sha256` integrity checks of every import in the worktree at
+/// `root`, without the `sha256:` prefix.
+pub fn project_hashes(root: &Path) -> Vec<String> {
+    let mut hashes: Vec<String> = Vec::new();
+    for file in worktree::dhall_files(root) {
+        let Ok(source) = fs::read_to_string(root.join(&file)) else {
+            continue;
+        };
+        for import in imports::imports(&source) {
+            let Some(hash) = import.hash else {
+                continue;
+            };
+            let hash = source[hash].trim_start_matches("sha256:").to_string();
+            if !hashes.contains(&hash) {
+                hashes.push(hash);
+            }
+        }
+    }
+    hashes
+}
+
+/// Returns the path of the cache entry for an import frozen with `hash`.
+pub fn entry_path(cache_dir: &Path, hash: &str) -> PathBuf {
+    cache_dir.join(format!("1220{hash}"))
+}
+
+/// Removes the cache entries of the project's frozen imports, or with `all`,
+/// every cached import.
+pub fn run_clear_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let cache_dir = cache_dir(worktree)?;
+    let text = match args.first().map(String::as_str) {
+        Some("all") => {
+            fs::remove_dir_all(&cache_dir)
+                .map_err(|e| format!("failed to remove {}: {e}", cache_dir.display()))?;
+            format!("Removed {}\n", cache_dir.display())
+        }
+        Some(_) => return Err("usage: /dhall-cache-clear [all]".to_string()),
+        None => {
+            let hashes = project_hashes(Path::new(&worktree.root_path()));
+            let removed = hashes
+                .iter()
+                .filter(|hash| fs::remove_file(entry_path(&cache_dir, hash)).is_ok())
+                .count();
+            format!(
+                "Removed {removed} cached imports ({} frozen imports in the project) from {}\n",
+                hashes.len(),
+                cache_dir.display()
+            )
+        }
+    };
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: "Dhall cache".to_string(),
+        }],
+        text,
+    })
+}
+
+/// Resolves every remote import in the project with `dhall resolve`, so that
+/// frozen imports are cached before the language server needs them.
+pub fn run_warm_slash_command(
+    _args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let dhall = cli::which(worktree, "dhall")?;
+    let root = PathBuf::from(worktree.root_path());
+    let mut seen: Vec<String> = Vec::new();
+    let mut failures = Vec::new();
+    for file in worktree::dhall_files(&root) {
+        let Ok(source) = fs::read_to_string(root.join(&file)) else {
+            continue;
+        };
+        for import in imports::imports(&source) {
+            if !import.target.starts_with("https://") && !import.target.starts_with("http://") {
+                continue;
+            }
+            let text = source[import.span].to_string();
+            if seen.contains(&text) {
+                continue;
+            }
+            let output = cli::run(&dhall, &["resolve"], &root, Some(&text))?;
+            if !output.success {
+                failures.push(format!(
+                    "- {} (in {file}): {}",
+                    import.target,
+                    output.stderr.trim()
+                ));
+            }
+            seen.push(text);
+        }
+    }
+
+    let mut text = format!(
+        "Resolved {} of {} remote imports\n",
+        seen.len() - failures.len(),
+        seen.len()
+    );
+    if !failures.is_empty() {
+        text.push_str(&format!("\nFailed:\n\n{}\n", failures.join("\n")));
+    }
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: "Dhall cache".to_string(),
+        }],
+        text,
+    })
+}
+
+/// The most cache entries `/dhall-cache-stats` lists individually.
+const MAX_LISTED: usize = 50;
+
+fn human_size(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{bytes} B"),
+        1024..=1048575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1048576.0),
+    }
+}
+
+/// Reports the size of the Dhall cache, and which of its entries the
+/// project's frozen imports refer to.
+pub fn run_stats_slash_command(
+    _args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let cache_dir = cache_dir(worktree)?;
+    let mut entries: Vec<(String, u64)> = fs::read_dir(&cache_dir)
+        .map_err(|e| format!("failed to read {}: {e}", cache_dir.display()))?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let hash = name.strip_prefix("1220")?.to_string();
+            Some((hash, entry.metadata().ok()?.len()))
+        })
+        .collect();
+    entries.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    let pinned = audit::find_imports(Path::new(&worktree.root_path()), |import| {
+        import.hash.is_some()
+    });
+    let urls = |hash: &str| -> Vec<&str> {
+        let mut urls: Vec<&str> = pinned
+            .iter()
+            .filter(|found| found.hash.as_deref() == Some(&format!("sha256:{hash}")))
+            .map(|found| found.import.target.as_str())
+            .collect();
+        urls.dedup();
+        urls
+    };
+
+    let total: u64 = entries.iter().map(|(_, size)| size).sum();
+    let (referenced, referenced_size) = entries
+        .iter()
+        .filter(|(hash, _)| !urls(hash).is_empty())
+        .fold((0, 0), |(count, total), (_, size)| {
+            (count + 1, total + size)
+        });
+    let mut text = format!(
+        "{}: {} entries, {}\n\nReferenced by this project: {referenced} entries, {}\n\
+         Not referenced: {} entries, {}\n\n",
+        cache_dir.display(),
+        entries.len(),
+        human_size(total),
+        human_size(referenced_size),
+        entries.len() - referenced,
+        human_size(total - referenced_size),
+    );
+    for (hash, size) in entries.iter().take(MAX_LISTED) {
+        let urls = urls(hash);
+        let used_by = if urls.is_empty() {
+            "not referenced".to_string()
+        } else {
+            urls.join(", ")
+        };
+        text.push_str(&format!(
+            "- {}: sha256:{}… ({used_by})\n",
+            human_size(*size),
+            &hash[..hash.len().min(12)]
+        ));
+    }
+    if entries.len() > MAX_LISTED {
+        text.push_str(&format!(
+            "- … and {} smaller entries\n",
+            entries.len() - MAX_LISTED
+        ));
+    }
+    text.push_str(
+        "\nRun `/dhall-cache-clear` to remove the project's entries, or \
+         `/dhall-cache-clear all` to empty the cache.\n",
+    );
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: "Dhall cache".to_string(),
+        }],
+        text,
+    })
+}
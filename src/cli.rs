@@ -0,0 +1,143 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::fs::{self, File};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use zed_extension_api::{self as zed, Result};
+
+use crate::{headers, offline};
+
+pub struct Output {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+pub fn executable_name(name: &str) -> String {
+    let (platform, _) = zed::current_platform();
+    if let zed::Os::Windows = platform {
+        format!("{name}.exe")
+    } else {
+        name.to_string()
+    }
+}
+
+/// A binary found on a worktree's `PATH`.
+struct Found {
+    worktree: u64,
+    binary_name: String,
+    path_var: String,
+    found_at: Instant,
+    path: String,
+}
+
+/// How long a binary found on the `PATH` is remembered. The sandbox can't
+/// see outside the extension's directory to check that it still exists.
+const FOUND_TTL: Duration = Duration::from_secs(5 * 60);
+
+static FOUND: Mutex<Vec<Found>> = Mutex::new(Vec::new());
+
+/// Looks up `binary_name` on the worktree's `$PATH`. Lookups are slow with a
+/// long `PATH` on a network filesystem, so binaries that are found are
+/// remembered for a while, unless the `PATH` changes. Misses aren't, so
+/// that a tool installed in the meantime is picked up.
+pub fn find_on_path(worktree: &zed::Worktree, binary_name: &str) -> Option<String> {
+    let path_var = worktree
+        .shell_env()
+        .into_iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("PATH"))
+        .map(|(_, value)| value)
+        .unwrap_or_default();
+    let mut found = FOUND.lock().ok()?;
+    found.retain(|found| found.found_at.elapsed() < FOUND_TTL);
+    if let Some(found) = found.iter().find(|found| {
+        found.worktree == worktree.id()
+            && found.binary_name == binary_name
+            && found.path_var == path_var
+    }) {
+        return Some(found.path.clone());
+    }
+    let path = worktree.which(binary_name)?;
+    found.push(Found {
+        worktree: worktree.id(),
+        binary_name: binary_name.to_string(),
+        path_var,
+        found_at: Instant::now(),
+        path: path.clone(),
+    });
+    Some(path)
+}
+
+/// Locates one of the Dhall command-line tools on the worktree's `$PATH`.
+pub fn which(worktree: &zed::Worktree, name: &str) -> Result<String> {
+    find_on_path(worktree, &executable_name(name))
+        .ok_or_else(|| format!("could not find `{name}` on the PATH"))
+}
+
+/// Numbers the files that hold commands' input.
+static STDIN_FILES: AtomicUsize = AtomicUsize::new(0);
+
+pub fn run(program: &str, args: &[&str], cwd: &Path, stdin: Option<&str>) -> Result<Output> {
+    // Only the Dhall tools are kept offline; `fly` and the like still need
+    // the network.
+    let is_dhall = Path::new(program)
+        .file_stem()
+        .is_some_and(|stem| stem.to_string_lossy().contains("dhall"));
+    // Writing to a pipe while the child's output goes unread deadlocks once
+    // both pipes fill, and the extension can't write from another thread,
+    // so the input is given to the child as a file instead, named for the
+    // time as well as a count since other Zed windows share the directory.
+    let input_path = stdin.map(|_| {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |now| now.as_nanos());
+        let count = STDIN_FILES.fetch_add(1, Ordering::Relaxed);
+        format!(".stdin-{now}-{count}")
+    });
+    let input = match (stdin, &input_path) {
+        (Some(input), Some(path)) => {
+            fs::write(path, input).map_err(|e| format!("failed to write {path}: {e}"))?;
+            File::open(path)
+                .map(Stdio::from)
+                .map_err(|e| format!("failed to read {path}: {e}"))
+        }
+        _ => Ok(Stdio::null()),
+    };
+    let output = input.and_then(|input| {
+        Command::new(program)
+            .args(args)
+            .envs(headers::process_env())
+            .envs(if is_dhall { offline::env() } else { Vec::new() })
+            .current_dir(cwd)
+            .stdin(input)
+            .output()
+            .map_err(|e| format!("failed to run {program}: {e:?}"))
+    });
+    if let Some(path) = input_path {
+        fs::remove_file(path).ok();
+    }
+    let output = output?;
+    Ok(Output {
+        success: output.status.success(),
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    })
+}
@@ -0,0 +1,115 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use zed_extension_api::{self as zed, Result, SlashCommandOutput, SlashCommandOutputSection};
+
+use crate::settings::DhallSettings;
+use crate::{cli, toolchain};
+
+pub const SLASH_COMMAND: &str = "dhall-concourse";
+
+/// Renders a pipeline file to YAML with `dhall-to-yaml`.
+fn render(worktree: &zed::Worktree, path: &str) -> Result<String> {
+    let dhall_to_yaml = toolchain::find(worktree, "dhall-json", "dhall-to-yaml")?;
+    let import = format!("./{}", path.trim_start_matches("./"));
+    let output = cli::run(
+        &dhall_to_yaml,
+        &[],
+        &PathBuf::from(worktree.root_path()),
+        Some(&import),
+    )?;
+    if !output.success {
+        return Err(format!("failed to render {path}: {}", output.stderr.trim()));
+    }
+    Ok(output.stdout)
+}
+
+/// Renders a pipeline, and with `set`, uploads it with `fly set-pipeline`
+/// to the target configured in `concourse.target`.
+pub fn run_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let usage = "usage: /dhall-concourse <file> [set]";
+    let path = args.first().ok_or_else(|| usage.to_string())?;
+    let set = match args.get(1).map(String::as_str) {
+        Some("set") => true,
+        Some(_) => return Err(usage.to_string()),
+        None => false,
+    };
+    let yaml = render(worktree, path)?;
+    if !set {
+        return Ok(SlashCommandOutput {
+            sections: vec![SlashCommandOutputSection {
+                range: (0..yaml.len()).into(),
+                label: format!("{path} as a Concourse pipeline"),
+            }],
+            text: yaml,
+        });
+    }
+
+    let settings = DhallSettings::for_worktree(worktree)?.concourse;
+    let target = settings
+        .target
+        .ok_or_else(|| "set lsp.dhall.settings.concourse.target to use `set`".to_string())?;
+    let pipeline = settings.pipeline.unwrap_or_else(|| {
+        Path::new(path)
+            .file_stem()
+            .map_or_else(|| path.clone(), |stem| stem.to_string_lossy().into_owned())
+    });
+
+    let config = env::current_dir()
+        .map_err(|e| format!("failed to locate working directory: {e}"))?
+        .join(format!("concourse-{pipeline}.yml"));
+    fs::write(&config, &yaml).map_err(|e| format!("failed to write pipeline: {e}"))?;
+    let fly = cli::which(worktree, "fly")?;
+    let output = cli::run(
+        &fly,
+        &[
+            "-t",
+            &target,
+            "set-pipeline",
+            "--non-interactive",
+            "-p",
+            &pipeline,
+            "-c",
+            &config.to_string_lossy(),
+        ],
+        &PathBuf::from(worktree.root_path()),
+        None,
+    );
+    fs::remove_file(&config).ok();
+    let output = output?;
+    if !output.success {
+        return Err(format!("fly set-pipeline failed: {}", output.stderr.trim()));
+    }
+
+    let text = format!(
+        "Set pipeline {pipeline} on {target}\n\n{}",
+        output.stdout.trim_end()
+    );
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: format!("fly set-pipeline {pipeline}"),
+        }],
+        text,
+    })
+}
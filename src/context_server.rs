@@ -0,0 +1,132 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use zed_extension_api::{self as zed, serde_json, Result};
+
+use crate::docs::{self, Entry};
+use crate::settings::ContextServerPermissions;
+use crate::{builtins, headers, offline, prelude, toolchain};
+
+pub const CONTEXT_SERVER_ID: &str = "dhall";
+
+/// The MCP server, which runs on Zed's Node.js runtime.
+const SERVER_SCRIPT: &str = include_str!("../context-server/server.js");
+const SCRIPT_DIR: &str = "context-server";
+
+/// The tools the server runs, with the release asset each comes from.
+const TOOLS: &[(&str, &str)] = &[("dhall", "dhall"), ("dhall-json", "dhall-to-json")];
+
+/// Collects the documentation the `search` tool looks through, by source:
+/// the builtins, the Prelude version the project imports, and each
+/// dhall-kubernetes version indexed with `/docs`. The Prelude is fetched if
+/// it hasn't been yet.
+fn search_index(roots: &[String]) -> BTreeMap<String, Vec<Entry>> {
+    let mut index = BTreeMap::new();
+    index.insert(
+        "Builtins".to_string(),
+        builtins::BUILTINS
+            .iter()
+            .map(|builtin| Entry {
+                name: builtin.name.to_string(),
+                signature: Some(builtin.signature.to_string()),
+                description: Some(builtin.doc.to_string()),
+            })
+            .collect(),
+    );
+
+    let versions: Vec<String> = roots
+        .iter()
+        .flat_map(|root| prelude::project_versions(Path::new(root)))
+        .collect();
+    let mut entries = docs::prelude::cached_entries(&versions);
+    if entries.is_empty() {
+        let version = versions
+            .first()
+            .cloned()
+            .or_else(|| prelude::latest_version().ok());
+        if let Some(version) = version {
+            entries = docs::prelude::entries(&version).unwrap_or_default();
+        }
+    }
+    index.insert("Prelude".to_string(), entries);
+
+    for (version, entries) in docs::kubernetes::cached_entries() {
+        index.insert(format!("dhall-kubernetes {version}"), entries);
+    }
+    index
+}
+
+/// Writes out the server script and its search index, and returns the
+/// command that runs it for the worktrees at `roots`. The `preamble` is
+/// given to the assistant as the server's instructions.
+pub fn command(
+    roots: &[String],
+    preamble: &str,
+    permissions: &ContextServerPermissions,
+) -> Result<zed::Command> {
+    fs::create_dir_all(SCRIPT_DIR).map_err(|e| format!("failed to create {SCRIPT_DIR}: {e}"))?;
+    let dir = env::current_dir()
+        .map_err(|e| format!("failed to locate working directory: {e}"))?
+        .join(SCRIPT_DIR);
+    let script = dir.join("server.js");
+    fs::write(&script, SERVER_SCRIPT).map_err(|e| format!("failed to write server: {e}"))?;
+    let index = dir.join("index.json");
+    let json = serde_json::to_string(&search_index(roots)).map_err(|e| e.to_string())?;
+    fs::write(&index, json).map_err(|e| format!("failed to write search index: {e}"))?;
+
+    // The context server has no worktree to look up the `PATH` in, so it
+    // can't decide whether to install anything itself. It falls back to the
+    // copies installed for slash commands instead.
+    let managed: Vec<String> = TOOLS
+        .iter()
+        .filter_map(|(package, tool)| toolchain::installed(package, tool))
+        .filter_map(|path| {
+            Path::new(&path)
+                .parent()
+                .map(|dir| dir.to_string_lossy().into_owned())
+        })
+        .collect();
+
+    let roots = serde_json::to_string(roots).map_err(|e| e.to_string())?;
+    let mut env = vec![
+        ("DHALL_MCP_ROOTS".to_string(), roots),
+        (
+            "DHALL_MCP_INDEX".to_string(),
+            index.to_string_lossy().into_owned(),
+        ),
+        ("DHALL_MCP_INSTRUCTIONS".to_string(), preamble.to_string()),
+        (
+            "DHALL_MCP_PATH".to_string(),
+            serde_json::to_string(&managed).map_err(|e| e.to_string())?,
+        ),
+        (
+            "DHALL_MCP_PERMISSIONS".to_string(),
+            serde_json::to_string(permissions).map_err(|e| e.to_string())?,
+        ),
+    ];
+    env.extend(headers::process_env());
+    env.extend(offline::env());
+    Ok(zed::Command {
+        command: zed::node_binary_path()?,
+        args: vec![script.to_string_lossy().into_owned()],
+        env,
+    })
+}
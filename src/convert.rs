@@ -0,0 +1,371 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::fs;
+use std::path::PathBuf;
+
+use zed_extension_api::{
+    self as zed, serde_json, Result, SlashCommandOutput, SlashCommandOutputSection,
+};
+
+use crate::docs::kubernetes;
+use crate::{cli, location, toolchain, worktree};
+
+pub const FROM_CSV_SLASH_COMMAND: &str = "dhall-from-csv";
+pub const FROM_K8S_SLASH_COMMAND: &str = "dhall-from-k8s";
+pub const MIGRATE_SLASH_COMMAND: &str = "dhall-migrate";
+pub const FROM_JSON_SLASH_COMMAND: &str = "dhall-from-json";
+pub const FROM_TOML_SLASH_COMMAND: &str = "dhall-from-toml";
+pub const FROM_YAML_SLASH_COMMAND: &str = "dhall-from-yaml";
+pub const OPENAPI_SLASH_COMMAND: &str = "dhall-openapi";
+pub const TO_BASH_SLASH_COMMAND: &str = "dhall-to-bash";
+pub const TO_CSV_SLASH_COMMAND: &str = "dhall-to-csv";
+pub const TO_NIX_SLASH_COMMAND: &str = "dhall-to-nix";
+pub const TO_TOML_SLASH_COMMAND: &str = "dhall-to-toml";
+
+/// Turns a worktree-relative file such as `types/Config.dhall` into an
+/// import; anything else is passed through as a Dhall expression.
+fn local_import(schema: &str) -> String {
+    let is_import = ["./", "../", "/", "~/", "https://", "http://", "env:"]
+        .iter()
+        .any(|prefix| schema.starts_with(prefix));
+    if !is_import && schema.ends_with(".dhall") {
+        format!("./{schema}")
+    } else {
+        schema.to_string()
+    }
+}
+
+/// Runs one of the `*-to-dhall` converters on `input`, against `schema` if
+/// one is given and otherwise inferring the type.
+fn to_dhall(
+    worktree: &zed::Worktree,
+    package: &str,
+    tool: &str,
+    input: &str,
+    schema: Option<&str>,
+) -> Result<String> {
+    let program = toolchain::find(worktree, package, tool)?;
+    let schema = schema.map(local_import);
+    let args: Vec<&str> = schema.iter().map(String::as_str).collect();
+    let output = cli::run(
+        &program,
+        &args,
+        &PathBuf::from(worktree.root_path()),
+        Some(input),
+    )?;
+    if !output.success {
+        return Err(format!("{tool} failed: {}", output.stderr.trim()));
+    }
+    Ok(output.stdout)
+}
+
+/// Runs one of the `dhall-to-*` renderers on the worktree file at `path`,
+/// importing it so that its relative imports resolve.
+fn from_dhall(
+    worktree: &zed::Worktree,
+    package: &str,
+    tool: &str,
+    path: &str,
+    args: &[&str],
+) -> Result<String> {
+    let program = toolchain::find(worktree, package, tool)?;
+    let import = local_import(path.trim_start_matches("./"));
+    let output = cli::run(
+        &program,
+        args,
+        &PathBuf::from(worktree.root_path()),
+        Some(&import),
+    )?;
+    if !output.success {
+        return Err(format!("{tool} failed: {}", output.stderr.trim()));
+    }
+    Ok(output.stdout)
+}
+
+fn output(label: String, text: String) -> SlashCommandOutput {
+    SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label,
+        }],
+        text,
+    }
+}
+
+/// Converts a JSON file (or JSON pasted as the argument) to Dhall.
+pub fn run_from_json_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let usage = "usage: /dhall-from-json <file.json> [schema] or /dhall-from-json <json>";
+    let pasted = args.join(" ");
+    if serde_json::from_str::<serde_json::Value>(&pasted).is_ok() {
+        let dhall = to_dhall(worktree, "dhall-json", "json-to-dhall", &pasted, None)?;
+        return Ok(output("JSON as Dhall".to_string(), dhall));
+    }
+
+    let path = args.first().ok_or_else(|| usage.to_string())?;
+    let schema = args.get(1).map(String::as_str);
+    let json = worktree.read_text_file(path)?;
+    let dhall = to_dhall(worktree, "dhall-json", "json-to-dhall", &json, schema)?;
+    Ok(output(format!("{path} as Dhall"), dhall))
+}
+
+/// Converts a YAML file to Dhall, e.g. to migrate Kubernetes manifests.
+pub fn run_from_yaml_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let path = args
+        .first()
+        .ok_or_else(|| "usage: /dhall-from-yaml <file.yaml> [schema]".to_string())?;
+    let schema = args.get(1).map(String::as_str);
+    let yaml = worktree.read_text_file(path)?;
+    let dhall = to_dhall(worktree, "dhall-yaml", "yaml-to-dhall", &yaml, schema)?;
+    Ok(output(format!("{path} as Dhall"), dhall))
+}
+
+/// Removes the indentation shared by every non-blank line, so that a block
+/// selected from within a larger document parses on its own.
+fn dedent(text: &str) -> String {
+    let indent = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    text.lines()
+        .map(|line| line.get(indent..).unwrap_or(line.trim_start()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Converts a block of JSON or YAML, given as the lines of any file, to
+/// Dhall, so configuration can be migrated a piece at a time.
+pub fn run_migrate_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let usage = || "usage: /dhall-migrate <file>:<first>-<last> [schema]".to_string();
+    let (path, lines) = location::parse_lines(args.first().ok_or_else(usage)?)?;
+    let schema = args.get(1).map(String::as_str);
+    let source = worktree.read_text_file(path)?;
+    let start = location::line_span(&source, *lines.start()).start;
+    let end = location::line_span(&source, *lines.end()).end;
+    let block = dedent(&source[start..end]);
+
+    let (format, dhall) = if serde_json::from_str::<serde_json::Value>(&block).is_ok() {
+        (
+            "JSON",
+            to_dhall(worktree, "dhall-json", "json-to-dhall", &block, schema)?,
+        )
+    } else {
+        (
+            "YAML",
+            to_dhall(worktree, "dhall-yaml", "yaml-to-dhall", &block, schema)?,
+        )
+    };
+    Ok(output(
+        format!(
+            "{format} from {path}:{}-{} as Dhall",
+            lines.start(),
+            lines.end()
+        ),
+        dhall,
+    ))
+}
+
+/// Renders a Dhall file as Bash, optionally as a `declare` statement.
+pub fn run_to_bash_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let usage = "usage: /dhall-to-bash <file> [--declare NAME]";
+    let path = args.first().ok_or_else(|| usage.to_string())?;
+    let declare = match args.get(1).map(String::as_str) {
+        Some("--declare") => Some(args.get(2).ok_or_else(|| usage.to_string())?),
+        Some(_) => return Err(usage.to_string()),
+        None => None,
+    };
+    let tool_args: Vec<&str> = match declare {
+        Some(name) => vec!["--declare", name],
+        None => Vec::new(),
+    };
+    let bash = from_dhall(worktree, "dhall-bash", "dhall-to-bash", path, &tool_args)?;
+    Ok(output(format!("{path} as Bash"), bash))
+}
+
+/// Renders a Dhall file as the Nix expression it compiles to.
+pub fn run_to_nix_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let path = args
+        .first()
+        .ok_or_else(|| "usage: /dhall-to-nix <file>".to_string())?;
+    let nix = from_dhall(worktree, "dhall-nix", "dhall-to-nix", path, &[])?;
+    Ok(output(format!("{path} as Nix"), nix))
+}
+
+/// Converts a TOML file to Dhall. `toml-to-dhall` cannot infer types, so a
+/// schema is required.
+pub fn run_from_toml_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let [path, schema] = args.as_slice() else {
+        return Err("usage: /dhall-from-toml <file.toml> <schema>".to_string());
+    };
+    let toml = worktree.read_text_file(path)?;
+    let dhall = to_dhall(worktree, "dhall-toml", "toml-to-dhall", &toml, Some(schema))?;
+    Ok(output(format!("{path} as Dhall"), dhall))
+}
+
+/// Renders a Dhall file as TOML.
+pub fn run_to_toml_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let path = args
+        .first()
+        .ok_or_else(|| "usage: /dhall-to-toml <file>".to_string())?;
+    let toml = from_dhall(worktree, "dhall-toml", "dhall-to-toml", path, &[])?;
+    Ok(output(format!("{path} as TOML"), toml))
+}
+
+/// Converts a CSV file with a header row to a Dhall list of records.
+pub fn run_from_csv_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let path = args
+        .first()
+        .ok_or_else(|| "usage: /dhall-from-csv <file.csv> [schema]".to_string())?;
+    let schema = args.get(1).map(String::as_str);
+    let csv = worktree.read_text_file(path)?;
+    let dhall = to_dhall(worktree, "dhall-csv", "csv-to-dhall", &csv, schema)?;
+    Ok(output(format!("{path} as Dhall"), dhall))
+}
+
+/// Renders a Dhall list of records as CSV.
+pub fn run_to_csv_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let path = args
+        .first()
+        .ok_or_else(|| "usage: /dhall-to-csv <file>".to_string())?;
+    let csv = from_dhall(worktree, "dhall-csv", "dhall-to-csv", path, &[])?;
+    Ok(output(format!("{path} as CSV"), csv))
+}
+
+/// Generates Dhall types and defaults from an OpenAPI (Swagger) spec into a
+/// directory of the worktree.
+pub fn run_openapi_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let [spec, output_dir] = args.as_slice() else {
+        return Err("usage: /dhall-openapi <spec.json> <output-dir>".to_string());
+    };
+    let root = PathBuf::from(worktree.root_path());
+    let spec_path = root.join(spec);
+    if !spec_path.is_file() {
+        return Err(format!("{spec} not found"));
+    }
+    let output_path = root.join(output_dir);
+    fs::create_dir_all(&output_path).map_err(|e| format!("failed to create {output_dir}: {e}"))?;
+
+    let program = toolchain::find(worktree, "dhall-openapi", "openapi-to-dhall")?;
+    let result = cli::run(
+        &program,
+        &[&spec_path.to_string_lossy()],
+        &output_path,
+        None,
+    )?;
+    if !result.success {
+        return Err(format!("openapi-to-dhall failed: {}", result.stderr.trim()));
+    }
+
+    let files = worktree::dhall_files(&output_path);
+    let mut text = format!(
+        "Generated {} files in {output_dir} from {spec}\n\n",
+        files.len()
+    );
+    for file in files.iter().filter(|file| !file.contains('/')) {
+        text.push_str(&format!("- {output_dir}/{file}\n"));
+    }
+    Ok(output(format!("Dhall types from {spec}"), text))
+}
+
+/// Converts a (possibly multi-document) Kubernetes manifest to Dhall, typing
+/// each document against the dhall-kubernetes type named by its `kind`.
+pub fn run_from_k8s_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let path = args
+        .first()
+        .ok_or_else(|| "usage: /dhall-from-k8s <file.yaml> [kubernetes-version]".to_string())?;
+    let version = args.get(1).map_or(kubernetes::VERSIONS[0], String::as_str);
+    let types = format!("{}/{version}/types.dhall", kubernetes::BASE_URL);
+    let yaml = worktree.read_text_file(path)?;
+
+    let mut text = String::new();
+    let mut sections = Vec::new();
+    let mut document = String::new();
+    let mut documents = Vec::new();
+    for line in yaml.lines() {
+        if line.trim_end() == "---" {
+            documents.push(std::mem::take(&mut document));
+        } else {
+            document.push_str(line);
+            document.push('\n');
+        }
+    }
+    documents.push(document);
+
+    for document in documents
+        .iter()
+        .filter(|document| !document.trim().is_empty())
+    {
+        let kind = document
+            .lines()
+            .find_map(|line| line.strip_prefix("kind:"))
+            .map(|kind| kind.trim().trim_matches(['"', '\'']))
+            .ok_or_else(|| format!("a document in {path} has no kind"))?;
+        let schema = format!("({types}).{kind}");
+        let dhall = to_dhall(
+            worktree,
+            "dhall-yaml",
+            "yaml-to-dhall",
+            document,
+            Some(&schema),
+        )?;
+        let start = text.len();
+        text.push_str(&dhall);
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        sections.push(SlashCommandOutputSection {
+            range: (start..text.len()).into(),
+            label: format!("{kind} (dhall-kubernetes {version})"),
+        });
+        text.push('\n');
+    }
+    Ok(SlashCommandOutput { text, sections })
+}
@@ -13,13 +13,60 @@
    See the License for the specific language governing permissions and
    limitations under the License.
 */
+
 use crate::language_server::*;
-use zed_extension_api as zed;
+use crate::settings::{ContextServerPermissions, DhallSettings};
+use zed_extension_api::{
+    self as zed,
+    lsp::{Completion, Symbol},
+    CodeLabel, ContextServerId, KeyValueStore, LanguageServerId, Project, SlashCommand,
+    SlashCommandArgumentCompletion, SlashCommandOutput, Worktree,
+};
 
+mod audit;
+mod builtins;
+mod cache;
+mod cli;
+mod concourse;
+mod context_server;
+mod convert;
+mod diagnostics;
+mod docs;
+mod eval;
+mod extract;
+mod graph;
+mod headers;
+mod import_docs;
+mod imports;
+mod inline;
+mod json_schema;
+mod labels;
 mod language_server;
+mod location;
+mod mirrors;
+mod offline;
+mod preamble;
+mod prelude;
+mod records;
+mod rename;
+mod scaffold;
+mod settings;
+mod symbols;
+mod syntax;
+mod test_runner;
+mod toolchain;
+mod type_search;
+mod vendor;
+mod worktree;
 
 struct DhallExtension {
     language_server: Option<DhallLanguageServer>,
+    worktree_roots: Vec<String>,
+    /// The root of each worktree by ID, for finding a project's roots.
+    worktree_ids: Vec<(u64, String)>,
+    docs_packages: Vec<String>,
+    /// The version preamble of each worktree by ID, for the context server.
+    preambles: Vec<(u64, String)>,
 }
 
 impl zed::Extension for DhallExtension {
@@ -28,7 +75,11 @@ impl zed::Extension for DhallExtension {
         Self: Sized,
     {
         Self {
-            language_server: None,
+            language_server: Some(DhallLanguageServer::new()),
+            worktree_roots: Vec::new(),
+            worktree_ids: Vec::new(),
+            docs_packages: Vec::new(),
+            preambles: Vec::new(),
         }
     }
 
@@ -37,15 +88,241 @@ impl zed::Extension for DhallExtension {
         language_server_id: &zed::LanguageServerId,
         worktree: &zed::Worktree,
     ) -> zed::Result<zed::Command> {
+        let root = worktree.root_path();
+        if !self.worktree_ids.iter().any(|(id, _)| *id == worktree.id()) {
+            self.worktree_ids.push((worktree.id(), root.clone()));
+        }
+        if !self.worktree_roots.contains(&root) {
+            self.worktree_roots.push(root);
+        }
+        let settings = DhallSettings::for_worktree(worktree)?;
+        let env = settings.apply(worktree)?;
+        let filter = language_server::Filter::new(&settings);
+        for package in settings.docs_packages {
+            if !self.docs_packages.contains(&package) {
+                self.docs_packages.push(package);
+            }
+        }
+
         if language_server_id.as_ref() == DhallLanguageServer::LANGUAGE_SERVER_ID {
             let language_server = self
                 .language_server
                 .get_or_insert_with(DhallLanguageServer::new);
-            language_server.language_server_command(language_server_id, worktree)
+            let mut command =
+                language_server.language_server_command(language_server_id, worktree)?;
+            command.env.extend(env);
+            let preamble = preamble::preamble(worktree, Some(&command.command));
+            self.preambles.retain(|(id, _)| *id != worktree.id());
+            self.preambles.push((worktree.id(), preamble));
+            if filter.is_active() {
+                command = language_server::filtered(command, &filter)?;
+            }
+            if settings.low_priority {
+                command = language_server::low_priority(command, worktree)?;
+            }
+            Ok(command)
         } else {
             Err(format!("unknown language server: {language_server_id}"))
         }
     }
+
+    fn language_server_workspace_configuration(
+        &mut self,
+        language_server_id: &LanguageServerId,
+        worktree: &Worktree,
+    ) -> zed::Result<Option<zed::serde_json::Value>> {
+        if let Some(language_server) = self.language_server.as_mut() {
+            // A failed check leaves the running server as it is, and is
+            // tried again the next day.
+            language_server.check_for_update().ok();
+        }
+        // Zed sends this to the server again, with
+        // `workspace/didChangeConfiguration`, whenever the settings change.
+        Ok(
+            zed::settings::LspSettings::for_worktree(language_server_id.as_ref(), worktree)?
+                .settings,
+        )
+    }
+
+    fn context_server_command(
+        &mut self,
+        context_server_id: &ContextServerId,
+        project: &Project,
+    ) -> zed::Result<zed::Command> {
+        if context_server_id.as_ref() != context_server::CONTEXT_SERVER_ID {
+            return Err(format!("unknown context server: {context_server_id}"));
+        }
+        // Worktrees are only known once the language server has started in
+        // them, so fall back to every known root.
+        let mut roots: Vec<String> = project
+            .worktree_ids()
+            .iter()
+            .filter_map(|id| {
+                self.worktree_ids
+                    .iter()
+                    .find(|(known, _)| known == id)
+                    .map(|(_, root)| root.clone())
+            })
+            .collect();
+        if roots.is_empty() {
+            roots = self.worktree_roots.clone();
+        }
+        let preambles: Vec<&str> = self
+            .preambles
+            .iter()
+            .filter(|(id, _)| project.worktree_ids().contains(id))
+            .map(|(_, preamble)| preamble.as_str())
+            .collect();
+        let permissions = ContextServerPermissions::for_project(project)?;
+        context_server::command(&roots, &preambles.concat(), &permissions)
+    }
+
+    fn label_for_completion(
+        &self,
+        _language_server_id: &LanguageServerId,
+        completion: Completion,
+    ) -> Option<CodeLabel> {
+        // Zed doesn't say which worktree the completion is for; the
+        // Prelude's entries are much the same in each.
+        let prelude = self
+            .worktree_roots
+            .iter()
+            .map(|root| docs::prelude::project_entries(root))
+            .find(|entries| !entries.is_empty())
+            .unwrap_or_default();
+        labels::completion_label(completion, &prelude)
+    }
+
+    fn label_for_symbol(
+        &self,
+        _language_server_id: &LanguageServerId,
+        symbol: Symbol,
+    ) -> Option<CodeLabel> {
+        labels::symbol_label(symbol)
+    }
+
+    fn complete_slash_command_argument(
+        &self,
+        command: SlashCommand,
+        args: Vec<String>,
+    ) -> zed::Result<Vec<SlashCommandArgumentCompletion>> {
+        match command.name.as_str() {
+            scaffold::SLASH_COMMAND => scaffold::complete_argument(args),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    fn run_slash_command(
+        &self,
+        command: SlashCommand,
+        args: Vec<String>,
+        worktree: Option<&Worktree>,
+    ) -> zed::Result<SlashCommandOutput> {
+        let worktree = worktree.ok_or_else(|| format!("/{} requires a worktree", command.name))?;
+        let settings = DhallSettings::for_worktree(worktree)?;
+        settings.apply(worktree)?;
+        match command.name.as_str() {
+            audit::FREEZE_ALL_SLASH_COMMAND => audit::run_freeze_all_slash_command(args, worktree),
+            audit::HASH_DRIFT_SLASH_COMMAND => audit::run_hash_drift_slash_command(args, worktree),
+            audit::STALE_SLASH_COMMAND => audit::run_stale_slash_command(args, worktree),
+            audit::UNPINNED_SLASH_COMMAND => audit::run_unpinned_slash_command(args, worktree),
+            cache::CLEAR_SLASH_COMMAND => cache::run_clear_slash_command(args, worktree),
+            cache::STATS_SLASH_COMMAND => cache::run_stats_slash_command(args, worktree),
+            cache::WARM_SLASH_COMMAND => cache::run_warm_slash_command(args, worktree),
+            concourse::SLASH_COMMAND => concourse::run_slash_command(args, worktree),
+            convert::FROM_CSV_SLASH_COMMAND => convert::run_from_csv_slash_command(args, worktree),
+            convert::FROM_JSON_SLASH_COMMAND => {
+                convert::run_from_json_slash_command(args, worktree)
+            }
+            convert::FROM_K8S_SLASH_COMMAND => convert::run_from_k8s_slash_command(args, worktree),
+            convert::FROM_TOML_SLASH_COMMAND => {
+                convert::run_from_toml_slash_command(args, worktree)
+            }
+            convert::FROM_YAML_SLASH_COMMAND => {
+                convert::run_from_yaml_slash_command(args, worktree)
+            }
+            convert::MIGRATE_SLASH_COMMAND => convert::run_migrate_slash_command(args, worktree),
+            convert::OPENAPI_SLASH_COMMAND => convert::run_openapi_slash_command(args, worktree),
+            convert::TO_BASH_SLASH_COMMAND => convert::run_to_bash_slash_command(args, worktree),
+            convert::TO_CSV_SLASH_COMMAND => convert::run_to_csv_slash_command(args, worktree),
+            convert::TO_NIX_SLASH_COMMAND => convert::run_to_nix_slash_command(args, worktree),
+            convert::TO_TOML_SLASH_COMMAND => convert::run_to_toml_slash_command(args, worktree),
+            diagnostics::FIX_SLASH_COMMAND => diagnostics::run_fix_slash_command(args, worktree),
+            diagnostics::SLASH_COMMAND => diagnostics::run_slash_command(args, worktree),
+            docs::bundle::SLASH_COMMAND => docs::bundle::run_slash_command(args, worktree),
+            eval::SLASH_COMMAND => eval::run_slash_command(args, worktree),
+            extract::SLASH_COMMAND => extract::run_slash_command(args, worktree),
+            graph::CYCLES_SLASH_COMMAND => graph::run_cycles_slash_command(args, worktree),
+            graph::IMPORTERS_SLASH_COMMAND => graph::run_importers_slash_command(args, worktree),
+            graph::SLASH_COMMAND => graph::run_slash_command(args, worktree),
+            import_docs::SLASH_COMMAND => import_docs::run_slash_command(args, worktree),
+            json_schema::FROM_JSON_SCHEMA_SLASH_COMMAND => {
+                json_schema::run_from_json_schema_slash_command(args, worktree)
+            }
+            json_schema::SLASH_COMMAND => json_schema::run_slash_command(args, worktree),
+            inline::SLASH_COMMAND => inline::run_slash_command(args, worktree),
+            mirrors::SLASH_COMMAND => mirrors::run_slash_command(args, worktree),
+            offline::SLASH_COMMAND => offline::run_slash_command(args, worktree),
+            preamble::SLASH_COMMAND => preamble::run_slash_command(
+                args,
+                worktree,
+                self.language_server
+                    .as_ref()
+                    .and_then(DhallLanguageServer::cached_binary_path),
+            ),
+            prelude::SLASH_COMMAND => prelude::run_slash_command(args, worktree),
+            prelude::BUMP_SLASH_COMMAND => prelude::run_bump_slash_command(args, worktree),
+            records::LET_TO_RECORD_SLASH_COMMAND => {
+                records::run_let_to_record_slash_command(args, worktree)
+            }
+            records::SORT_FIELDS_SLASH_COMMAND => {
+                records::run_sort_fields_slash_command(args, worktree)
+            }
+            rename::SLASH_COMMAND => rename::run_slash_command(args, worktree),
+            scaffold::SLASH_COMMAND => scaffold::run_slash_command(args, worktree),
+            symbols::SLASH_COMMAND => symbols::run_slash_command(args, worktree),
+            test_runner::SLASH_COMMAND => test_runner::run_slash_command(args, worktree),
+            toolchain::SLASH_COMMAND => toolchain::run_slash_command(
+                args,
+                worktree,
+                self.language_server
+                    .as_ref()
+                    .and_then(DhallLanguageServer::cached_binary_path),
+            ),
+            type_search::SLASH_COMMAND => type_search::run_slash_command(args, worktree),
+            vendor::SLASH_COMMAND => vendor::run_slash_command(args, worktree),
+            name => Err(format!("unknown slash command: {name}")),
+        }
+    }
+
+    fn suggest_docs_packages(&self, provider: String) -> zed::Result<Vec<String>> {
+        match provider.as_str() {
+            docs::kubernetes::PROVIDER => Ok(docs::kubernetes::suggest_packages()),
+            docs::local::PROVIDER => Ok(docs::local::suggest_packages(&self.worktree_roots)),
+            docs::prelude::PROVIDER => Ok(docs::prelude::suggest_packages(&self.worktree_roots)),
+            docs::project::PROVIDER => Ok(docs::project::suggest_packages(&self.worktree_roots)),
+            docs::remote::PROVIDER => Ok(docs::remote::suggest_packages(&self.docs_packages)),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    fn index_docs(
+        &self,
+        provider: String,
+        package: String,
+        database: &KeyValueStore,
+    ) -> zed::Result<()> {
+        match provider.as_str() {
+            docs::kubernetes::PROVIDER => docs::kubernetes::index(&package, database),
+            docs::local::PROVIDER => docs::local::index(&package, database),
+            docs::prelude::PROVIDER => {
+                docs::prelude::index(&package, &self.worktree_roots, database)
+            }
+            docs::project::PROVIDER => docs::project::index(&package, database),
+            docs::remote::PROVIDER => docs::remote::index(&package, database),
+            provider => Err(format!("unknown docs provider: {provider}")),
+        }
+    }
 }
 
 zed::register_extension!(DhallExtension);
@@ -0,0 +1,151 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::path::{Path, PathBuf};
+
+use zed_extension_api::{self as zed, Result, SlashCommandOutput, SlashCommandOutputSection};
+
+use crate::{cli, imports, syntax};
+
+pub const SLASH_COMMAND: &str = "dhall-errors";
+pub const FIX_SLASH_COMMAND: &str = "dhall-fix";
+
+/// Imports whose types are longer than this are summarized rather than
+/// included in `/dhall-fix`'s prompt.
+const MAX_IMPORT_TYPE_BYTES: usize = 4 * 1024;
+
+/// The result of type-checking a file.
+pub enum Check {
+    /// The file's type.
+    Type(String),
+    /// The error, with `--explain`'s full explanation.
+    Error(String),
+}
+
+/// Type-checks the file at `path`, relative to `root`.
+pub fn check(dhall: &str, root: &Path, path: &str) -> Result<Check> {
+    let output = cli::run(dhall, &["--explain", "type", "--file", path], root, None)?;
+    Ok(if output.success {
+        Check::Type(output.stdout.trim().to_string())
+    } else {
+        Check::Error(output.stderr.trim().to_string())
+    })
+}
+
+/// Adds a file's type errors, fully explained, to the conversation.
+pub fn run_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let path = args
+        .first()
+        .ok_or_else(|| "usage: /dhall-errors <file>".to_string())?
+        .trim_start_matches("./");
+    let dhall = cli::which(worktree, "dhall")?;
+
+    let text = match check(&dhall, &PathBuf::from(worktree.root_path()), path)? {
+        Check::Type(file_type) => {
+            format!("`{path}` type-checks, with type:\n\n```dhall\n{file_type}\n```\n")
+        }
+        Check::Error(error) => format!("`{path}` fails to type-check:\n\n```\n{error}\n```\n"),
+    };
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: format!("Errors in {path}"),
+        }],
+        text,
+    })
+}
+
+/// Gathers a file, the types of its imports and its type error into a
+/// prompt asking the assistant for a fix.
+pub fn run_fix_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let path = args
+        .first()
+        .ok_or_else(|| "usage: /dhall-fix <file>".to_string())?
+        .trim_start_matches("./");
+    let dhall = cli::which(worktree, "dhall")?;
+    let root = PathBuf::from(worktree.root_path());
+    let error = match check(&dhall, &root, path)? {
+        Check::Type(_) => return Err(format!("{path} type-checks: there is nothing to fix")),
+        Check::Error(error) => error,
+    };
+    let source = worktree.read_text_file(path)?;
+    let cwd = root
+        .join(path)
+        .parent()
+        .map_or(root.clone(), Path::to_path_buf);
+
+    let mut text = String::new();
+    let mut sections = Vec::new();
+    let mut section = |text: &mut String, label: String, body: String| {
+        let start = text.len();
+        text.push_str(&body);
+        sections.push(SlashCommandOutputSection {
+            range: (start..text.len()).into(),
+            label,
+        });
+    };
+
+    section(
+        &mut text,
+        format!("Source of {path}"),
+        format!("`{path}`:\n\n```dhall\n{}\n```\n\n", source.trim_end()),
+    );
+
+    let bindings = syntax::let_chain(&source).bindings;
+    let mut types = String::new();
+    for import in imports::imports(&source) {
+        let name = bindings
+            .iter()
+            .find(|binding| {
+                binding.value.start <= import.span.start && import.span.end <= binding.value.end
+            })
+            .map(|binding| format!("`{}` = ", binding.name))
+            .unwrap_or_default();
+        let output = cli::run(&dhall, &["type"], &cwd, Some(&source[import.span.clone()]))?;
+        let import_type = output.stdout.trim();
+        let import_type = if !output.success {
+            format!("(fails to type-check: {})", output.stderr.trim())
+        } else if import_type.len() > MAX_IMPORT_TYPE_BYTES {
+            format!("(a type of {} KiB, omitted)", import_type.len() / 1024)
+        } else {
+            format!("\n\n```dhall\n{import_type}\n```")
+        };
+        types.push_str(&format!("- {name}`{}`: {import_type}\n", import.target));
+    }
+    if !types.is_empty() {
+        section(
+            &mut text,
+            "Types of its imports".to_string(),
+            format!("The types of its imports:\n\n{types}\n"),
+        );
+    }
+
+    section(
+        &mut text,
+        "Type error".to_string(),
+        format!("The type error:\n\n```\n{error}\n```\n\n"),
+    );
+    text.push_str(&format!(
+        "Explain the cause of this type error in `{path}` and propose a fix.\n"
+    ));
+    Ok(SlashCommandOutput { text, sections })
+}
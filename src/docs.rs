@@ -0,0 +1,185 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use serde::{Deserialize, Serialize};
+use zed_extension_api::{
+    http_client::{HttpMethod, HttpRequest, RedirectPolicy},
+    KeyValueStore, Result,
+};
+
+use crate::{headers, imports, mirrors, offline, records, syntax};
+
+pub mod bundle;
+pub mod kubernetes;
+pub mod local;
+pub mod prelude;
+pub mod project;
+pub mod remote;
+
+/// Fetches `url`, preferring the copy in the offline docs bundle.
+pub fn fetch_text(url: &str) -> Result<String> {
+    if let Some(text) = bundle::read(url) {
+        return Ok(text);
+    }
+    fetch_remote(url)
+}
+
+fn fetch_remote(url: &str) -> Result<String> {
+    if offline::is_enabled() {
+        return Err(format!("not fetching {url} in offline mode"));
+    }
+    let url = &mirrors::remap(url);
+    let mut request = HttpRequest::builder()
+        .method(HttpMethod::Get)
+        .url(url)
+        .redirect_policy(RedirectPolicy::FollowAll);
+    for (name, value) in headers::for_url(url) {
+        request = request.header(name, value);
+    }
+    let response = request
+        .build()?
+        .fetch()
+        .map_err(|e| format!("failed to fetch {url}: {e}"))?;
+    String::from_utf8(response.body).map_err(|e| format!("{url} is not UTF-8: {e}"))
+}
+
+/// The documentation for a single Dhall file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub name: String,
+    pub signature: Option<String>,
+    pub description: Option<String>,
+}
+
+impl Entry {
+    pub fn parse(name: &str, source: &str) -> Self {
+        let chain = syntax::let_chain(source);
+        let signature = chain
+            .bindings
+            .first()
+            .and_then(|binding| binding.annotation.clone())
+            .map(|annotation| collapse_whitespace(&source[annotation]));
+        Self {
+            name: name.to_string(),
+            signature,
+            description: syntax::leading_doc_comment(source),
+        }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("# {}\n\n", self.name);
+        if let Some(signature) = &self.signature {
+            markdown.push_str(&format!("```dhall\n{} : {signature}\n```\n\n", self.name));
+        }
+        if let Some(description) = &self.description {
+            markdown.push_str(description);
+            markdown.push('\n');
+        }
+        markdown
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Returns the fields of a `package.dhall` record along with the relative
+/// import each one refers to.
+pub fn package_entries(source: &str) -> Vec<(String, String)> {
+    let Some(record) = records::records(source)
+        .into_iter()
+        .max_by_key(|record| record.span.len())
+    else {
+        return Vec::new();
+    };
+    record
+        .fields
+        .iter()
+        .filter_map(|field| {
+            let import = imports::imports(&source[field.span.clone()])
+                .into_iter()
+                .find(|import| import.target.starts_with("./"))?;
+            Some((field.name.clone(), import.target))
+        })
+        .collect()
+}
+
+/// Resolves a relative import against the URL of the file containing it.
+pub fn join_url(base: &str, relative: &str) -> Option<String> {
+    let (origin, path) = base.find("://").and_then(|scheme| {
+        let host_end = base[scheme + 3..].find('/')? + scheme + 3;
+        Some((&base[..host_end], &base[host_end..]))
+    })?;
+    let mut segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    segments.pop();
+    for segment in relative.split('/') {
+        match segment {
+            "." | "" => {}
+            ".." => {
+                segments.pop()?;
+            }
+            segment => segments.push(segment),
+        }
+    }
+    Some(format!("{origin}/{}", segments.join("/")))
+}
+
+/// Indexes the package record at `url`, descending into nested packages.
+/// Entries are keyed by their field path, e.g. `Kubernetes/Deployment`.
+pub fn index_package(
+    url: &str,
+    prefix: &str,
+    database: &KeyValueStore,
+    depth: usize,
+) -> Result<()> {
+    let source = fetch_text(url)?;
+    let mut index = format!(
+        "# {}\n\n{url}\n\n",
+        if prefix.is_empty() { url } else { prefix }
+    );
+    if let Some(description) = syntax::leading_doc_comment(&source) {
+        index.push_str(&format!("{description}\n\n"));
+    }
+
+    for (name, target) in package_entries(&source) {
+        let key = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{prefix}/{name}")
+        };
+        let Some(entry_url) = join_url(url, &target) else {
+            continue;
+        };
+        if target.ends_with("package.dhall") && depth > 0 {
+            if index_package(&entry_url, &key, database, depth - 1).is_ok() {
+                index.push_str(&format!("- `{key}` (package)\n"));
+            }
+            continue;
+        }
+        let Ok(source) = fetch_text(&entry_url) else {
+            continue;
+        };
+        let entry = Entry::parse(&key, &source);
+        index.push_str(&format!("- `{key}`"));
+        if let Some(signature) = &entry.signature {
+            index.push_str(&format!(" : `{signature}`"));
+        }
+        index.push('\n');
+        database.insert(&key, &entry.to_markdown())?;
+    }
+
+    database.insert(if prefix.is_empty() { url } else { prefix }, &index)
+}
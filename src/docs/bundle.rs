@@ -0,0 +1,108 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use zed_extension_api::{self as zed, Result, SlashCommandOutput, SlashCommandOutputSection};
+
+use super::{fetch_remote, join_url, package_entries};
+use crate::prelude;
+use crate::settings::DhallSettings;
+
+pub const SLASH_COMMAND: &str = "dhall-docs-bundle";
+
+/// Where downloaded files are kept, relative to the extension's working
+/// directory. Each file lives at its URL without the scheme.
+const BUNDLE_DIR: &str = "docs-bundle";
+
+/// How many levels of nested `package.dhall` records to download.
+const MAX_DEPTH: usize = 3;
+
+fn bundle_path(url: &str) -> Option<PathBuf> {
+    let (_, path) = url.split_once("://")?;
+    let path = path.split(['?', '#']).next()?;
+    if path.split('/').any(|segment| segment == "..") {
+        return None;
+    }
+    Some(Path::new(BUNDLE_DIR).join(path))
+}
+
+/// Returns the bundled copy of `url`, if it has been downloaded.
+pub fn read(url: &str) -> Option<String> {
+    fs::read_to_string(bundle_path(url)?).ok()
+}
+
+/// Fetches `url` and stores it in the bundle, replacing any older copy.
+fn download(url: &str) -> Result<String> {
+    let text = fetch_remote(url)?;
+    let path = bundle_path(url).ok_or_else(|| format!("cannot bundle {url}"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("failed to create {}: {e}", dir.display()))?;
+    }
+    fs::write(&path, &text).map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+    Ok(text)
+}
+
+/// Downloads the package record at `url` and every file it refers to,
+/// returning the number of files stored.
+fn download_package(url: &str, depth: usize) -> Result<usize> {
+    let source = download(url)?;
+    let mut count = 1;
+    for (_, target) in package_entries(&source) {
+        let Some(entry_url) = join_url(url, &target) else {
+            continue;
+        };
+        if target.ends_with("package.dhall") && depth > 0 {
+            count += download_package(&entry_url, depth - 1).unwrap_or_default();
+        } else if download(&entry_url).is_ok() {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+pub fn run_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let settings = DhallSettings::for_worktree(worktree)?;
+    let version = match args.first() {
+        Some(version) => prelude::normalize_version(version),
+        None => prelude::docs_version(worktree)?,
+    };
+
+    let mut text = String::new();
+    let prelude_files = download_package(&prelude::package_url(&version), MAX_DEPTH)?;
+    text.push_str(&format!("- Prelude {version}: {prelude_files} files\n"));
+    for package in &settings.docs_packages {
+        match download_package(package, MAX_DEPTH) {
+            Ok(files) => text.push_str(&format!("- {package}: {files} files\n")),
+            Err(e) => text.push_str(&format!("- {package}: {e}\n")),
+        }
+    }
+    text.insert_str(
+        0,
+        "Downloaded the docs bundle; docs are now served from disk:\n\n",
+    );
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: format!("Docs bundle (Prelude {version})"),
+        }],
+        text,
+    })
+}
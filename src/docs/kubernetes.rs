@@ -0,0 +1,183 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use zed_extension_api::{serde_json, KeyValueStore, Result};
+
+use super::{collapse_whitespace, fetch_text, package_entries, Entry};
+use crate::{records, syntax};
+
+pub const PROVIDER: &str = "dhall-kubernetes";
+
+pub const BASE_URL: &str = "https://raw.githubusercontent.com/dhall-lang/dhall-kubernetes/master";
+
+/// The Kubernetes versions published by dhall-kubernetes, newest first.
+pub const VERSIONS: &[&str] = &["1.31", "1.30", "1.29", "1.28", "1.27", "1.26"];
+
+pub fn suggest_packages() -> Vec<String> {
+    VERSIONS.iter().map(|version| version.to_string()).collect()
+}
+
+/// Returns each field of the outermost record in `source`, split at the
+/// first `separator` into its name and (whitespace-collapsed) type or value.
+pub fn record_fields(source: &str, separator: char) -> Vec<(String, String)> {
+    let Some(record) = records::records(source)
+        .into_iter()
+        .max_by_key(|record| record.span.len())
+    else {
+        return Vec::new();
+    };
+    record
+        .fields
+        .into_iter()
+        .filter_map(|field| {
+            let (_, rest) = source[field.span].split_once(separator)?;
+            Some((field.name, collapse_whitespace(rest)))
+        })
+        .collect()
+}
+
+/// Returns the alternatives of a union type such as
+/// `< Int : Natural | String : Text >`.
+fn union_alternatives(source: &str) -> Vec<String> {
+    let tokens = syntax::code_tokens(source);
+    if !tokens.first().is_some_and(|token| token.is(source, "<")) {
+        return Vec::new();
+    }
+    let close = syntax::matching_close(&tokens, 0);
+    let inner = &tokens[1..close];
+    let mut alternatives = Vec::new();
+    let mut start = 0;
+    while start < inner.len() {
+        let end = syntax::find_top_level(inner, start, |token| token.is(source, "|"));
+        let span = syntax::span_between(inner, start, end);
+        if !span.is_empty() {
+            alternatives.push(collapse_whitespace(&source[span]));
+        }
+        start = end + 1;
+    }
+    alternatives
+}
+
+fn cache_path(version: &str) -> String {
+    format!("kubernetes-{version}.json")
+}
+
+/// Returns the types and fields of each Kubernetes version indexed so far,
+/// by version.
+pub fn cached_entries() -> Vec<(String, Vec<Entry>)> {
+    VERSIONS
+        .iter()
+        .filter_map(|version| {
+            let json = fs::read_to_string(cache_path(version)).ok()?;
+            Some((version.to_string(), serde_json::from_str(&json).ok()?))
+        })
+        .collect()
+}
+
+/// Indexes every type in the given Kubernetes version, along with its
+/// default values, under the type's short name (e.g. `Deployment`). Each
+/// field is indexed as e.g. `Container.imagePullPolicy`, and each field name
+/// (e.g. `imagePullPolicy`) lists the types in which it can be set. The types
+/// and fields are also cached in the working directory for the context
+/// server's `search` tool.
+pub fn index(package: &str, database: &KeyValueStore) -> Result<()> {
+    if !package.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return Err(format!("invalid Kubernetes version: {package}"));
+    }
+    let base = format!("{BASE_URL}/{package}");
+    let types = fetch_text(&format!("{base}/types.dhall"))?;
+
+    let mut index = format!("# dhall-kubernetes {package}\n\n");
+    let mut field_owners: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut entries = Vec::new();
+    for (name, target) in package_entries(&types) {
+        let path = target.trim_start_matches("./");
+        let Ok(schema) = fetch_text(&format!("{base}/{path}")) else {
+            continue;
+        };
+        let qualified = path
+            .trim_start_matches("types/")
+            .trim_end_matches(".dhall")
+            .to_string();
+        let defaults = fetch_text(&format!("{base}/default/{qualified}.dhall")).ok();
+
+        let mut markdown = format!(
+            "# {name}\n\n`{qualified}`\n\n## Type\n\n```dhall\n{}\n```\n",
+            schema.trim()
+        );
+        if let Some(defaults) = &defaults {
+            markdown.push_str(&format!(
+                "\n## Defaults\n\n```dhall\n{}\n```\n",
+                defaults.trim()
+            ));
+        }
+
+        let alternatives = union_alternatives(&schema);
+        if !alternatives.is_empty() {
+            markdown.push_str("\n## Alternatives\n\n");
+            for alternative in &alternatives {
+                markdown.push_str(&format!("- `{name}.{alternative}`\n"));
+            }
+        }
+
+        let default_values = defaults
+            .as_deref()
+            .map(|defaults| record_fields(defaults, '='))
+            .unwrap_or_default();
+        for (field, field_type) in record_fields(&schema, ':') {
+            let key = format!("{name}.{field}");
+            let mut field_markdown = format!(
+                "# {key}\n\n```dhall\n{field} : {field_type}\n```\n\nSet with `Kubernetes.{name}::{{ {field} = … }}`.\n"
+            );
+            let default = match default_values.iter().find(|(name, _)| *name == field) {
+                Some((_, value)) => format!("Defaults to `{value}`."),
+                None => "Required: there is no default.".to_string(),
+            };
+            field_markdown.push_str(&format!("\n{default}\n"));
+            database.insert(&key, &field_markdown)?;
+            entries.push(Entry {
+                name: key,
+                signature: Some(field_type),
+                description: Some(default),
+            });
+            field_owners.entry(field).or_default().push(name.clone());
+        }
+
+        database.insert(&name, &markdown)?;
+        entries.push(Entry {
+            name: name.clone(),
+            signature: Some(collapse_whitespace(&schema)),
+            description: Some(format!("The `{qualified}` type.")),
+        });
+        index.push_str(&format!("- `{name}` (`{qualified}`)\n"));
+    }
+
+    for (field, owners) in field_owners {
+        let mut markdown = format!("# {field}\n\nSet in:\n\n");
+        for owner in owners {
+            markdown.push_str(&format!("- `{owner}.{field}`\n"));
+        }
+        database.insert(&field, &markdown)?;
+    }
+    database.insert(package, &index)?;
+    if let Ok(json) = serde_json::to_string(&entries) {
+        fs::write(cache_path(package), json).ok();
+    }
+    Ok(())
+}
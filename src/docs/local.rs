@@ -0,0 +1,120 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use zed_extension_api::{KeyValueStore, Result};
+
+pub const PROVIDER: &str = "dhall-docs";
+
+/// The default `--output-link` of `dhall-docs`.
+const OUTPUT_DIR: &str = "docs";
+
+/// Suggests the `dhall-docs` output directory of each known worktree.
+pub fn suggest_packages(worktree_roots: &[String]) -> Vec<String> {
+    worktree_roots
+        .iter()
+        .map(|root| Path::new(root).join(OUTPUT_DIR))
+        .filter(|dir| dir.join("index.html").is_file())
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Indexes the HTML pages generated by `dhall-docs` in the directory
+/// `package`, keyed by the path of the documented file.
+pub fn index(package: &str, database: &KeyValueStore) -> Result<()> {
+    let root = PathBuf::from(package);
+    if !root.join("index.html").is_file() {
+        return Err(format!("{package} does not contain dhall-docs output"));
+    }
+
+    let mut pending = vec![root.clone()];
+    while let Some(dir) = pending.pop() {
+        let entries =
+            fs::read_dir(&dir).map_err(|e| format!("failed to list {}: {e}", dir.display()))?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            if path.extension().and_then(|ext| ext.to_str()) != Some("html") {
+                continue;
+            }
+            let Ok(html) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let key = path
+                .strip_prefix(&root)
+                .unwrap_or(&path)
+                .with_extension("")
+                .to_string_lossy()
+                .replace('\\', "/");
+            database.insert(&key, &html_to_text(&html))?;
+        }
+    }
+    Ok(())
+}
+
+/// Strips the markup from an HTML page, keeping its text content.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len() / 2);
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let lower = rest.get(..7).unwrap_or(rest).to_ascii_lowercase();
+        let skip_until = if lower.starts_with("<script") {
+            Some("</script>")
+        } else if lower.starts_with("<style") {
+            Some("</style>")
+        } else {
+            None
+        };
+        let end = match skip_until {
+            Some(close) => rest.find(close).map(|ix| ix + close.len()),
+            None => rest.find('>').map(|ix| ix + 1),
+        };
+        let Some(end) = end else {
+            rest = "";
+            break;
+        };
+        let tag = &rest[..end];
+        if tag.starts_with("<br")
+            || tag.starts_with("</p")
+            || tag.starts_with("</div")
+            || tag.starts_with("</h")
+            || tag.starts_with("</li")
+            || tag.starts_with("</pre")
+        {
+            text.push('\n');
+        }
+        rest = &rest[end..];
+    }
+    text.push_str(rest);
+
+    let text = text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&");
+    let mut lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+    lines.dedup_by(|a, b| a.trim().is_empty() && b.trim().is_empty());
+    lines.join("\n").trim().to_string()
+}
@@ -0,0 +1,232 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use zed_extension_api::{serde_json, KeyValueStore, Result};
+
+use super::{fetch_text, package_entries, Entry};
+use crate::{builtins, prelude};
+
+pub const PROVIDER: &str = "dhall-prelude";
+
+/// The modules of the Prelude, as named in its top-level `package.dhall`.
+const MODULES: &[&str] = &[
+    "Bool", "Double", "Function", "Integer", "JSON", "List", "Location", "Map", "Monoid",
+    "Natural", "NonEmpty", "Operator", "Optional", "Text", "XML",
+];
+
+/// Suggests the Prelude and its modules at each version imported by the
+/// known worktrees (e.g. `List@v23.0.0`), so that moving to a new version
+/// yields a fresh index rather than reusing one built from the old version.
+/// Without any imports, the unversioned names index the latest release.
+pub fn suggest_packages(worktree_roots: &[String]) -> Vec<String> {
+    let mut versions: Vec<String> = Vec::new();
+    for root in worktree_roots {
+        for version in prelude::project_versions(Path::new(root)) {
+            if !versions.contains(&version) {
+                versions.push(version);
+            }
+        }
+    }
+
+    let mut packages = vec!["Builtins".to_string()];
+    if versions.is_empty() {
+        packages.push("Prelude".to_string());
+        packages.extend(MODULES.iter().map(|module| module.to_string()));
+    }
+    for version in versions {
+        packages.push(format!("Prelude@{version}"));
+        packages.extend(MODULES.iter().map(|module| format!("{module}@{version}")));
+    }
+    packages
+}
+
+/// Indexes the language's builtins and keywords from the static table.
+fn index_builtins(database: &KeyValueStore) -> Result<()> {
+    let mut index = "# Builtins\n\n".to_string();
+    for builtin in builtins::BUILTINS {
+        let entry = Entry {
+            name: builtin.name.to_string(),
+            signature: Some(builtin.signature.to_string()),
+            description: Some(builtin.doc.to_string()),
+        };
+        database.insert(builtin.name, &entry.to_markdown())?;
+        index.push_str(&format!("- `{}` : `{}`\n", builtin.name, builtin.signature));
+    }
+    index.push_str("\n# Keywords\n\n");
+    for keyword in builtins::KEYWORDS {
+        let markdown = format!(
+            "# {}\n\n```dhall\n{}\n```\n\n{}\n",
+            keyword.name, keyword.syntax, keyword.doc
+        );
+        database.insert(keyword.name, &markdown)?;
+        index.push_str(&format!("- `{}`: {}\n", keyword.syntax, keyword.doc));
+    }
+    database.insert("Builtins", &index)
+}
+
+/// Returns the documented entries of a Prelude module. They are fetched on
+/// first use and cached in the extension's working directory once every one
+/// of them has been fetched.
+pub fn module_entries(version: &str, module: &str) -> Result<Vec<Entry>> {
+    if let Some(entries) = cached_module_entries(version, module) {
+        return Ok(entries);
+    }
+    let cache_dir = format!("prelude-{version}");
+    let cache = format!("{cache_dir}/{module}.json");
+
+    let base = format!("https://prelude.dhall-lang.org/{version}/{module}");
+    let package_source = fetch_text(&format!("{base}/package.dhall"))?;
+    let mut entries = Vec::new();
+    let mut complete = true;
+    for (name, target) in package_entries(&package_source) {
+        let path = target.trim_start_matches("./");
+        let Ok(source) = fetch_text(&format!("{base}/{path}")) else {
+            complete = false;
+            continue;
+        };
+        entries.push(Entry::parse(&format!("{module}/{name}"), &source));
+    }
+
+    // A module missing some of its functions isn't cached, so that they're
+    // fetched again next time rather than hidden for good.
+    if complete && fs::create_dir_all(&cache_dir).is_ok() {
+        if let Ok(json) = serde_json::to_string(&entries) {
+            fs::write(&cache, json).ok();
+        }
+    }
+    Ok(entries)
+}
+
+fn cached_module_entries(version: &str, module: &str) -> Option<Vec<Entry>> {
+    let json = fs::read_to_string(format!("prelude-{version}/{module}.json")).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+/// How long the Prelude entries found for a worktree are used before its
+/// files are read again, in case it moved to another release.
+const PROJECT_ENTRIES_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// The Prelude entries of a worktree.
+struct ProjectEntries {
+    root: String,
+    found_at: Instant,
+    entries: Arc<[Entry]>,
+}
+
+static PROJECT_ENTRIES: Mutex<Vec<ProjectEntries>> = Mutex::new(Vec::new());
+
+/// The cached entries of the Prelude release the worktree at `root`
+/// imports, for completion labels. Finding the release reads every file in
+/// the worktree, so it's done on the first completion rather than when the
+/// language server starts.
+pub fn project_entries(root: &str) -> Arc<[Entry]> {
+    let Ok(mut found) = PROJECT_ENTRIES.lock() else {
+        return Arc::default();
+    };
+    found.retain(|found| found.found_at.elapsed() < PROJECT_ENTRIES_TTL);
+    if let Some(found) = found.iter().find(|found| found.root == root) {
+        return found.entries.clone();
+    }
+    let versions = prelude::project_versions(Path::new(root));
+    let entries: Arc<[Entry]> = cached_entries(&versions).into();
+    found.push(ProjectEntries {
+        root: root.to_string(),
+        found_at: Instant::now(),
+        entries: entries.clone(),
+    });
+    entries
+}
+
+/// Returns whatever Prelude entries have already been fetched, without
+/// touching the network. The first of `versions` with a cache is used,
+/// falling back to the newest cached version.
+pub fn cached_entries(versions: &[String]) -> Vec<Entry> {
+    let mut cached: Vec<String> = fs::read_dir(".")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            name.strip_prefix("prelude-").map(str::to_string)
+        })
+        .collect();
+    cached.sort_by_key(|version| prelude::version_key(version));
+    let Some(version) = versions
+        .iter()
+        .find(|version| cached.contains(version))
+        .or(cached.last())
+    else {
+        return Vec::new();
+    };
+    MODULES
+        .iter()
+        .filter_map(|module| cached_module_entries(version, module))
+        .flatten()
+        .collect()
+}
+
+/// Returns the documented entries of every Prelude module.
+pub fn entries(version: &str) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for module in MODULES {
+        entries.extend(module_entries(version, module)?);
+    }
+    Ok(entries)
+}
+
+/// Indexes a Prelude module (or all of them) at the version given after an
+/// `@`, or else the newest version imported by the known worktrees.
+pub fn index(package: &str, worktree_roots: &[String], database: &KeyValueStore) -> Result<()> {
+    if package == "Builtins" {
+        return index_builtins(database);
+    }
+    let imported = worktree_roots.iter().find_map(|root| {
+        prelude::project_versions(Path::new(root))
+            .into_iter()
+            .next()
+    });
+    let (package, version) = match (package.split_once('@'), imported) {
+        (Some((package, version)), _) => (package, prelude::normalize_version(version)),
+        (None, Some(version)) => (package, version),
+        (None, None) => (package, prelude::latest_version()?),
+    };
+    let modules: Vec<&str> = if package == "Prelude" {
+        MODULES.to_vec()
+    } else if MODULES.contains(&package) {
+        vec![package]
+    } else {
+        return Err(format!("unknown Prelude module: {package}"));
+    };
+
+    for module in modules {
+        let mut index = format!("# {module}\n\nPrelude {version}\n\n");
+        for entry in module_entries(&version, module)? {
+            index.push_str(&format!("- `{}`", entry.name));
+            if let Some(signature) = &entry.signature {
+                index.push_str(&format!(" : `{signature}`"));
+            }
+            index.push('\n');
+            database.insert(&entry.name, &entry.to_markdown())?;
+        }
+        database.insert(module, &index)?;
+    }
+    Ok(())
+}
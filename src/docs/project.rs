@@ -0,0 +1,83 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::fs;
+use std::path::Path;
+
+use zed_extension_api::{KeyValueStore, Result};
+
+use super::{collapse_whitespace, Entry};
+use crate::{syntax, worktree};
+
+pub const PROVIDER: &str = "dhall-project";
+
+pub fn suggest_packages(worktree_roots: &[String]) -> Vec<String> {
+    worktree_roots.to_vec()
+}
+
+/// Indexes the documentation comments of every file in the worktree at
+/// `package`, and of each documented top-level binding (as `file#name`).
+pub fn index(package: &str, database: &KeyValueStore) -> Result<()> {
+    let root = Path::new(package);
+    if !root.is_dir() {
+        return Err(format!("{package} is not a directory"));
+    }
+
+    let mut index = format!("# {package}\n\n");
+    for file in worktree::dhall_files(root) {
+        let Ok(source) = fs::read_to_string(root.join(&file)) else {
+            continue;
+        };
+        let mut entry = Entry::parse(&file, &source);
+        let chain = syntax::let_chain(&source);
+        let mut documented = Vec::new();
+        for binding in &chain.bindings {
+            let Some(description) = syntax::doc_comment_before(&source, binding.span.start) else {
+                continue;
+            };
+            // The first binding's comment usually doubles as the file's.
+            if entry.description.as_ref() == Some(&description) {
+                continue;
+            }
+            let binding_entry = Entry {
+                name: format!("{file}#{}", binding.name),
+                signature: binding
+                    .annotation
+                    .clone()
+                    .map(|annotation| collapse_whitespace(&source[annotation])),
+                description: Some(description),
+            };
+            database.insert(&binding_entry.name, &binding_entry.to_markdown())?;
+            documented.push(binding_entry.name);
+        }
+
+        if entry.description.is_none() && documented.is_empty() {
+            continue;
+        }
+        if !documented.is_empty() {
+            let bindings = documented
+                .iter()
+                .map(|name| format!("- `{name}`"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let description = entry.description.take().unwrap_or_default();
+            entry.description = Some(format!("{description}\n\n{bindings}").trim().to_string());
+        }
+        database.insert(&file, &entry.to_markdown())?;
+        index.push_str(&format!("- `{file}`\n"));
+    }
+    database.insert(package, &index)
+}
@@ -0,0 +1,55 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use zed_extension_api::{KeyValueStore, Result};
+
+pub const PROVIDER: &str = "dhall-package";
+
+/// How many levels of nested `package.dhall` records to follow.
+const MAX_DEPTH: usize = 3;
+
+/// Well-known ecosystem packages that can be indexed by name.
+const KNOWN_PACKAGES: &[(&str, &str)] = &[
+    (
+        "dhall-concourse",
+        "https://raw.githubusercontent.com/coralogix/dhall-concourse/master/package.dhall",
+    ),
+    (
+        "dhall-packages",
+        "https://raw.githubusercontent.com/EarnestResearch/dhall-packages/master/package.dhall",
+    ),
+];
+
+/// Suggests the well-known packages followed by those in `docs_packages`.
+pub fn suggest_packages(docs_packages: &[String]) -> Vec<String> {
+    KNOWN_PACKAGES
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .chain(docs_packages.iter().cloned())
+        .collect()
+}
+
+/// Indexes the Dhall package at the URL `package`, or the well-known package
+/// of that name.
+pub fn index(package: &str, database: &KeyValueStore) -> Result<()> {
+    if let Some((name, url)) = KNOWN_PACKAGES.iter().find(|(name, _)| *name == package) {
+        return super::index_package(url, name, database, MAX_DEPTH);
+    }
+    if !(package.starts_with("https://") || package.starts_with("http://")) {
+        return Err(format!("expected a package URL, got {package}"));
+    }
+    super::index_package(package, "", database, MAX_DEPTH)
+}
@@ -0,0 +1,93 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::path::{Path, PathBuf};
+
+use zed_extension_api::{self as zed, Result, SlashCommandOutput, SlashCommandOutputSection};
+
+use crate::{cli, location, syntax};
+
+pub const SLASH_COMMAND: &str = "dhall-eval";
+
+/// Builds a program that evaluates just the top-level binding on `line`,
+/// keeping the bindings before it in scope. Lines outside of any binding
+/// evaluate the body of the file.
+pub fn program_at_line(source: &str, line: usize) -> (String, String) {
+    let chain = syntax::let_chain(source);
+    let line_span = location::line_span(source, line);
+    let target = chain.bindings.iter().position(|binding| {
+        binding.span.start <= line_span.end && line_span.start < binding.span.end
+    });
+
+    let (bindings, name, expression) = match target {
+        Some(ix) => (
+            &chain.bindings[..ix],
+            chain.bindings[ix].name.clone(),
+            &source[chain.bindings[ix].value.clone()],
+        ),
+        None => (
+            &chain.bindings[..],
+            "<body>".to_string(),
+            &source[chain.body.clone()],
+        ),
+    };
+    if bindings.is_empty() {
+        return (name, format!("{expression}\n"));
+    }
+
+    let mut program = String::new();
+    for binding in bindings {
+        program.push_str(&source[binding.span.clone()]);
+        program.push('\n');
+    }
+    program.push_str(&format!("in {expression}\n"));
+    (name, program)
+}
+
+pub fn run_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let (path, line) = location::parse(
+        args.first()
+            .ok_or_else(|| "usage: /dhall-eval <file>:<line>".to_string())?,
+    )?;
+    let source = worktree.read_text_file(path)?;
+    let (name, program) = program_at_line(&source, line);
+
+    let dhall = cli::which(worktree, "dhall")?;
+    let root = PathBuf::from(worktree.root_path());
+    let cwd = root
+        .join(path)
+        .parent()
+        .map_or(root.clone(), Path::to_path_buf);
+    let output = cli::run(&dhall, &[], &cwd, Some(&program))?;
+    if !output.success {
+        return Err(format!(
+            "failed to evaluate {name}: {}",
+            output.stderr.trim()
+        ));
+    }
+
+    let text = output.stdout;
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: format!("{name} in {path}"),
+        }],
+        text,
+    })
+}
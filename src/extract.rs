@@ -0,0 +1,118 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use zed_extension_api::{self as zed, Result, SlashCommandOutput, SlashCommandOutputSection};
+
+use crate::{imports, location};
+
+pub const SLASH_COMMAND: &str = "dhall-extract";
+
+pub fn run_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let usage = || "usage: /dhall-extract <file>:<first>-<last> <new-file>".to_string();
+    let (path, lines) = location::parse_lines(args.first().ok_or_else(usage)?)?;
+    let path = &worktree_path(path)?;
+    let new_path = args.get(1).ok_or_else(usage)?;
+    let new_path = if new_path.ends_with(".dhall") {
+        worktree_path(new_path)?
+    } else {
+        worktree_path(&format!("{new_path}.dhall"))?
+    };
+
+    let source = worktree.read_text_file(path)?;
+    let line_count = source.lines().count();
+    if *lines.start() == 0 || *lines.end() > line_count {
+        return Err(format!(
+            "{path} has lines 1-{line_count}, not {}-{}",
+            lines.start(),
+            lines.end()
+        ));
+    }
+    let start = location::line_span(&source, *lines.start()).start;
+    let end = location::line_span(&source, *lines.end()).end;
+    let selection = &source[start..end];
+    let indent = &selection[..selection.len() - selection.trim_start().len()];
+
+    let root = PathBuf::from(worktree.root_path());
+    let destination = root.join(&new_path);
+    if destination.exists() {
+        return Err(format!("{new_path} already exists"));
+    }
+    if let Some(dir) = destination.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("failed to create {}: {e}", dir.display()))?;
+    }
+    fs::write(&destination, format!("{}\n", selection.trim()))
+        .map_err(|e| format!("failed to write {new_path}: {e}"))?;
+
+    let rewritten = format!(
+        "{}{indent}{}{}",
+        &source[..start],
+        imports::relative_import(path, &new_path),
+        &source[end..]
+    );
+    if let Err(e) = fs::write(root.join(path), rewritten) {
+        // Don't leave the expression in two places.
+        fs::remove_file(&destination).ok();
+        return Err(format!("failed to write {path}: {e}"));
+    }
+
+    let text = format!(
+        "Moved lines {}-{} of {path} into {new_path}, and imported it in their place\n",
+        lines.start(),
+        lines.end()
+    );
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: format!("{path} importing {new_path}"),
+        }],
+        text,
+    })
+}
+
+/// Returns `file` relative to the worktree root, refusing absolute paths and
+/// those that lead outside it.
+fn worktree_path(file: &str) -> Result<String> {
+    if Path::new(file).is_absolute() || file.starts_with(['/', '\\']) {
+        return Err(format!("{file} must be relative to the worktree"));
+    }
+    imports::resolve_local("", &format!("./{file}"))
+        .filter(|path| !path.is_empty())
+        .ok_or_else(|| format!("{file} is outside the worktree"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_paths_within_the_worktree() {
+        assert_eq!(worktree_path("./a/../b.dhall"), Ok("b.dhall".to_string()));
+        assert_eq!(worktree_path("a/b.dhall"), Ok("a/b.dhall".to_string()));
+    }
+
+    #[test]
+    fn refuses_paths_outside_the_worktree() {
+        assert!(worktree_path("../../x.dhall").is_err());
+        assert!(worktree_path("a/../../x.dhall").is_err());
+        assert!(worktree_path("/tmp/x.dhall").is_err());
+    }
+}
@@ -0,0 +1,415 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use zed_extension_api::{self as zed, Result, SlashCommandOutput, SlashCommandOutputSection};
+
+use crate::{imports, worktree};
+
+pub const SLASH_COMMAND: &str = "dhall-import-graph";
+pub const CYCLES_SLASH_COMMAND: &str = "dhall-cycles";
+pub const IMPORTERS_SLASH_COMMAND: &str = "dhall-importers";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    /// A file in the worktree, whose imports are followed.
+    Local,
+    Remote,
+    Env,
+    /// A path outside the worktree, or one that couldn't be read.
+    Other,
+}
+
+pub struct Node {
+    pub name: String,
+    pub kind: NodeKind,
+}
+
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub pinned: bool,
+}
+
+#[derive(Default)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+impl Graph {
+    fn node(&mut self, name: &str, kind: NodeKind) -> (usize, bool) {
+        if let Some(ix) = self.nodes.iter().position(|node| node.name == name) {
+            return (ix, false);
+        }
+        self.nodes.push(Node {
+            name: name.to_string(),
+            kind,
+        });
+        (self.nodes.len() - 1, true)
+    }
+
+    /// Walks the imports of `entry` (relative to the worktree at `root`),
+    /// following local files and stopping at remote and environment imports.
+    pub fn build(root: &Path, entry: &str) -> Self {
+        let mut graph = Self::default();
+        let (entry_ix, _) = graph.node(entry, NodeKind::Local);
+        let mut pending = vec![entry_ix];
+        while let Some(from) = pending.pop() {
+            let file = graph.nodes[from].name.clone();
+            let Ok(source) = fs::read_to_string(root.join(&file)) else {
+                graph.nodes[from].kind = NodeKind::Other;
+                continue;
+            };
+            for import in imports::imports(&source) {
+                let (name, kind) = if import.target.starts_with("https://")
+                    || import.target.starts_with("http://")
+                {
+                    (import.target.clone(), NodeKind::Remote)
+                } else if import.target.starts_with("env:") {
+                    (import.target.clone(), NodeKind::Env)
+                } else if let Some(path) = imports::resolve_local(&file, &import.target) {
+                    (path, NodeKind::Local)
+                } else {
+                    (import.target.clone(), NodeKind::Other)
+                };
+                let (to, is_new) = graph.node(&name, kind);
+                if is_new && kind == NodeKind::Local {
+                    pending.push(to);
+                }
+                let edge = Edge {
+                    from,
+                    to,
+                    pinned: import.hash.is_some(),
+                };
+                if !graph
+                    .edges
+                    .iter()
+                    .any(|e| e.from == edge.from && e.to == edge.to)
+                {
+                    graph.edges.push(edge);
+                }
+            }
+        }
+        graph
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = "digraph imports {\n  rankdir=LR;\n".to_string();
+        for (ix, node) in self.nodes.iter().enumerate() {
+            let shape = match node.kind {
+                NodeKind::Local => "box",
+                NodeKind::Remote => "ellipse",
+                NodeKind::Env => "diamond",
+                NodeKind::Other => "plaintext",
+            };
+            dot.push_str(&format!(
+                "  n{ix} [label={:?}, shape={shape}];\n",
+                node.name
+            ));
+        }
+        for edge in &self.edges {
+            let style = if edge.pinned { "solid" } else { "dashed" };
+            dot.push_str(&format!(
+                "  n{} -> n{} [style={style}];\n",
+                edge.from, edge.to
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = "flowchart LR\n".to_string();
+        for (ix, node) in self.nodes.iter().enumerate() {
+            let name = node.name.replace('"', "#quot;");
+            let shape = match node.kind {
+                NodeKind::Local => format!("[\"{name}\"]"),
+                NodeKind::Remote => format!("([\"{name}\"])"),
+                NodeKind::Env => format!("{{\"{name}\"}}"),
+                NodeKind::Other => format!(">\"{name}\"]"),
+            };
+            mermaid.push_str(&format!("  n{ix}{shape}\n"));
+        }
+        for edge in &self.edges {
+            let arrow = if edge.pinned { "-->" } else { "-.->" };
+            mermaid.push_str(&format!("  n{} {arrow} n{}\n", edge.from, edge.to));
+        }
+        mermaid
+    }
+}
+
+pub fn run_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let usage = "usage: /dhall-import-graph <file> [dot|mermaid]";
+    let entry = args
+        .first()
+        .ok_or_else(|| usage.to_string())?
+        .trim_start_matches("./");
+    let graph = Graph::build(&PathBuf::from(worktree.root_path()), entry);
+    let (language, body) = match args.get(1).map(String::as_str) {
+        Some("dot") => ("dot", graph.to_dot()),
+        Some("mermaid") | None => ("mermaid", graph.to_mermaid()),
+        Some(_) => return Err(usage.to_string()),
+    };
+
+    let text = format!(
+        "```{language}\n{body}```\n\nDashed edges are imports without a sha256 integrity check.\n"
+    );
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: format!("Imports of {entry}"),
+        }],
+        text,
+    })
+}
+
+/// Returns a `(file, imported file)` pair for each local import in the
+/// worktree at `root`.
+fn local_imports(root: &Path) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    for file in worktree::dhall_files(root) {
+        let Ok(source) = fs::read_to_string(root.join(&file)) else {
+            continue;
+        };
+        for import in imports::imports(&source) {
+            if let Some(path) = imports::resolve_local(&file, &import.target) {
+                pairs.push((file.clone(), path));
+            }
+        }
+    }
+    pairs
+}
+
+/// Returns each worktree file that imports `target`, directly or through
+/// other files, with the chain of imports leading from it to `target`.
+/// Direct importers come first.
+pub fn importers(root: &Path, target: &str) -> Vec<Vec<String>> {
+    let imported_by: Vec<(String, String)> = local_imports(root)
+        .into_iter()
+        .map(|(file, path)| (path, file))
+        .collect();
+
+    let mut chains: Vec<Vec<String>> = Vec::new();
+    let mut pending = vec![vec![target.to_string()]];
+    while !pending.is_empty() {
+        let mut next = Vec::new();
+        for chain in pending {
+            for (_, importer) in imported_by.iter().filter(|(path, _)| *path == chain[0]) {
+                let seen = importer == target || chains.iter().any(|c| c[0] == *importer);
+                if seen {
+                    continue;
+                }
+                let mut longer = vec![importer.clone()];
+                longer.extend(chain.iter().cloned());
+                chains.push(longer.clone());
+                next.push(longer);
+            }
+        }
+        pending = next;
+    }
+    chains
+}
+
+pub fn run_importers_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let target = args
+        .first()
+        .ok_or_else(|| "usage: /dhall-importers <file>".to_string())?
+        .trim_start_matches("./");
+    let chains = importers(&PathBuf::from(worktree.root_path()), target);
+
+    let mut text = String::new();
+    if chains.is_empty() {
+        text.push_str(&format!("No files import {target}.\n"));
+    }
+    for chain in &chains {
+        if chain.len() == 2 {
+            text.push_str(&format!("- {}\n", chain[0]));
+        } else {
+            text.push_str(&format!(
+                "- {} (via {})\n",
+                chain[0],
+                chain[1..].join(" → ")
+            ));
+        }
+    }
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: format!("Importers of {target}"),
+        }],
+        text,
+    })
+}
+
+/// Returns the import cycles among the worktree's files, each as the chain
+/// of files starting and ending with the same file.
+pub fn cycles(root: &Path) -> Vec<Vec<String>> {
+    let edges = local_imports(root);
+    let mut files: Vec<&str> = edges.iter().map(|(file, _)| file.as_str()).collect();
+    files.dedup();
+
+    let mut cycles: Vec<Vec<String>> = Vec::new();
+    let mut done: Vec<&str> = Vec::new();
+    for start in files {
+        if done.contains(&start) {
+            continue;
+        }
+        // A depth-first walk, where `stack` holds the path from `start` and
+        // each entry's index of the next import to follow.
+        let mut stack: Vec<(&str, usize)> = vec![(start, 0)];
+        while let Some((file, next)) = stack.last_mut() {
+            let file = *file;
+            let Some((_, to)) = edges.iter().filter(|(from, _)| from == file).nth(*next) else {
+                done.push(file);
+                stack.pop();
+                continue;
+            };
+            *next += 1;
+            if let Some(ix) = stack.iter().position(|(on_stack, _)| *on_stack == to) {
+                let mut cycle: Vec<String> = stack[ix..]
+                    .iter()
+                    .map(|(file, _)| file.to_string())
+                    .collect();
+                // Rotate so the same cycle found from another file compares equal.
+                let min = (0..cycle.len()).min_by_key(|&i| &cycle[i]).unwrap_or(0);
+                cycle.rotate_left(min);
+                cycle.push(cycle[0].clone());
+                if !cycles.contains(&cycle) {
+                    cycles.push(cycle);
+                }
+            } else if !done.contains(&to.as_str()) {
+                stack.push((to, 0));
+            }
+        }
+    }
+    cycles
+}
+
+pub fn run_cycles_slash_command(
+    _args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let cycles = cycles(&PathBuf::from(worktree.root_path()));
+
+    let mut text = String::new();
+    if cycles.is_empty() {
+        text.push_str("No import cycles.\n");
+    } else {
+        text.push_str(&format!(
+            "{} import cycles, which Dhall rejects when resolving any file on them:\n\n",
+            cycles.len()
+        ));
+    }
+    for cycle in &cycles {
+        text.push_str(&format!("- {}\n", cycle.join(" → ")));
+    }
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: "Import cycles".to_string(),
+        }],
+        text,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `files` into a fresh directory named for the test.
+    fn project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("dhall-graph-{name}-{}", std::process::id()));
+        fs::remove_dir_all(&root).ok();
+        for (file, source) in files {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, source).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn finds_cycles_once() {
+        let root = project(
+            "cycles",
+            &[
+                ("a.dhall", "./b.dhall"),
+                ("b.dhall", "./c.dhall"),
+                ("c.dhall", "./a.dhall"),
+                ("d.dhall", "./a.dhall"),
+                ("self.dhall", "./self.dhall"),
+            ],
+        );
+        let mut found = cycles(&root);
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                vec!["a.dhall", "b.dhall", "c.dhall", "a.dhall"],
+                vec!["self.dhall", "self.dhall"],
+            ]
+        );
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn finds_no_cycles_in_a_tree() {
+        let root = project(
+            "acyclic",
+            &[
+                ("a.dhall", "{ b = ./b.dhall, c = ./lib/c.dhall }"),
+                ("b.dhall", "./lib/c.dhall"),
+                ("lib/c.dhall", "1"),
+            ],
+        );
+        assert!(cycles(&root).is_empty());
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn finds_direct_importers_first_with_their_chains() {
+        let root = project(
+            "importers",
+            &[
+                ("app.dhall", "./lib/config.dhall"),
+                ("lib/config.dhall", "./types.dhall"),
+                ("lib/types.dhall", "{ x : Natural }"),
+                ("other.dhall", "./lib/types.dhall"),
+            ],
+        );
+        let mut chains = importers(&root, "lib/types.dhall");
+        // Direct importers come first; within a level, order follows the walk.
+        chains[..2].sort();
+        assert_eq!(
+            chains,
+            vec![
+                vec!["lib/config.dhall", "lib/types.dhall"],
+                vec!["other.dhall", "lib/types.dhall"],
+                vec!["app.dhall", "lib/config.dhall", "lib/types.dhall"],
+            ]
+        );
+        fs::remove_dir_all(root).ok();
+    }
+}
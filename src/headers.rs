@@ -0,0 +1,217 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use zed_extension_api::Result;
+
+/// Header names and values to send to each `host` or `host:port`.
+pub type Headers = BTreeMap<String, BTreeMap<String, String>>;
+
+/// The inline headers from the most recently read settings, sent with the
+/// extension's own HTTP requests.
+static CURRENT: Mutex<Headers> = Mutex::new(BTreeMap::new());
+
+/// The environment for the `dhall` processes the extension runs.
+static PROCESS_ENV: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+/// Checks that each key is a `host[:port]` and each header name a token.
+fn validate(headers: &Headers) -> Result<()> {
+    for (host, fields) in headers {
+        if host.is_empty() || host.contains("://") || host.contains('/') {
+            return Err(format!(
+                "invalid host {host:?} in headers: use host or host:port"
+            ));
+        }
+        for name in fields.keys() {
+            let is_token = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c));
+            if !is_token {
+                return Err(format!("invalid header name {name:?} for {host}"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Adds the default port for `scheme` to `host`, unless it has one, since
+/// `dhall` matches headers on `host:port`.
+fn with_port(host: &str, scheme: &str) -> String {
+    match (host.contains(':'), scheme) {
+        (true, _) => host.to_string(),
+        (false, "http") => format!("{host}:80"),
+        (false, _) => format!("{host}:443"),
+    }
+}
+
+/// The `host:port` entries `host` stands for: itself if it gives a port,
+/// and otherwise the host over both HTTPS and plain HTTP.
+fn authorities(host: &str) -> Vec<String> {
+    if host.contains(':') {
+        vec![host.to_string()]
+    } else {
+        vec![with_port(host, "https"), with_port(host, "http")]
+    }
+}
+
+fn dhall_text(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('$', "\\u0024");
+    format!("\"{escaped}\"")
+}
+
+/// Renders `headers` as the value of `DHALL_HEADERS`.
+pub fn to_dhall(headers: &Headers) -> String {
+    let hosts: Vec<String> = headers
+        .iter()
+        .filter(|(_, fields)| !fields.is_empty())
+        .flat_map(|(host, fields)| {
+            authorities(host)
+                .into_iter()
+                .map(move |authority| (authority, fields))
+        })
+        .map(|(authority, fields)| {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(name, value)| {
+                    format!(
+                        "{{ mapKey = {}, mapValue = {} }}",
+                        dhall_text(name),
+                        dhall_text(value)
+                    )
+                })
+                .collect();
+            format!(
+                "{{ mapKey = {}, mapValue = [ {} ] }}",
+                dhall_text(&authority),
+                fields.join(", ")
+            )
+        })
+        .collect();
+    if hosts.is_empty() {
+        "[] : List { mapKey : Text, mapValue : List { mapKey : Text, mapValue : Text } }"
+            .to_string()
+    } else {
+        format!("[ {} ]", hosts.join(", "))
+    }
+}
+
+/// Returns the environment to give `dhall` processes: `DHALL_HEADERS`, from
+/// `headers_file` (relative to `root`) or the inline `headers`.
+fn env(
+    root: &Path,
+    headers: &Headers,
+    headers_file: Option<&str>,
+) -> Result<Vec<(String, String)>> {
+    let value = match headers_file {
+        Some(_) if !headers.is_empty() => {
+            return Err("set either headers or headers_file, not both".to_string())
+        }
+        Some(file) => fs::read_to_string(root.join(file))
+            .map_err(|e| format!("failed to read headers_file {file}: {e}"))?,
+        None if headers.is_empty() => return Ok(Vec::new()),
+        None => to_dhall(headers),
+    };
+    Ok(vec![("DHALL_HEADERS".to_string(), value)])
+}
+
+/// Validates the header settings and makes them the ones in effect,
+/// returning the environment to give the language server.
+pub fn apply(
+    root: &Path,
+    headers: &Headers,
+    headers_file: Option<&str>,
+) -> Result<Vec<(String, String)>> {
+    validate(headers)?;
+    let env = env(root, headers, headers_file)?;
+    if let Ok(mut current) = CURRENT.lock() {
+        *current = headers.clone();
+    }
+    if let Ok(mut process_env) = PROCESS_ENV.lock() {
+        *process_env = env.clone();
+    }
+    Ok(env)
+}
+
+pub fn process_env() -> Vec<(String, String)> {
+    PROCESS_ENV
+        .lock()
+        .map(|env| env.clone())
+        .unwrap_or_default()
+}
+
+/// Returns the headers to send with a request to `url`.
+pub fn for_url(url: &str) -> Vec<(String, String)> {
+    let Some((scheme, authority)) = url
+        .split_once("://")
+        .and_then(|(scheme, rest)| Some((scheme, rest.split(['/', '?', '#']).next()?)))
+    else {
+        return Vec::new();
+    };
+    let authority = with_port(authority, scheme);
+    let Ok(current) = CURRENT.lock() else {
+        return Vec::new();
+    };
+    current
+        .iter()
+        .filter(|(host, _)| authorities(host).contains(&authority))
+        .flat_map(|(_, fields)| fields.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_the_scheme_default_port() {
+        assert_eq!(with_port("example.com", "https"), "example.com:443");
+        assert_eq!(with_port("example.com", "http"), "example.com:80");
+        assert_eq!(with_port("example.com:8080", "http"), "example.com:8080");
+    }
+
+    #[test]
+    fn renders_bare_hosts_for_both_schemes() {
+        let headers = Headers::from([(
+            "example.com".to_string(),
+            BTreeMap::from([("Authorization".to_string(), "token $x".to_string())]),
+        )]);
+        // `$` is escaped so that it can't start an interpolation.
+        let fields = r#"[ { mapKey = "Authorization", mapValue = "token \u0024x" } ]"#;
+        assert_eq!(
+            to_dhall(&headers),
+            format!(
+                r#"[ {{ mapKey = "example.com:443", mapValue = {fields} }}, {{ mapKey = "example.com:80", mapValue = {fields} }} ]"#
+            )
+        );
+    }
+
+    #[test]
+    fn renders_explicit_ports_alone() {
+        let headers = Headers::from([(
+            "example.com:443".to_string(),
+            BTreeMap::from([("X".to_string(), "y".to_string())]),
+        )]);
+        assert!(!to_dhall(&headers).contains(":80"));
+    }
+}
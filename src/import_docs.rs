@@ -0,0 +1,72 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use zed_extension_api::{self as zed, Result, SlashCommandOutput, SlashCommandOutputSection};
+
+use crate::docs::{self, Entry};
+use crate::{imports, location};
+
+pub const SLASH_COMMAND: &str = "dhall-import-docs";
+
+/// Describes the import on a line: its documentation comment, type, and
+/// (for a package record) the fields it provides.
+pub fn run_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let (path, line) = location::parse(
+        args.first()
+            .ok_or_else(|| "usage: /dhall-import-docs <file>:<line>".to_string())?,
+    )?;
+    let source = worktree.read_text_file(path)?;
+    let line_span = location::line_span(&source, line);
+    let import = imports::imports(&source)
+        .into_iter()
+        .find(|import| line_span.contains(&import.span.start))
+        .ok_or_else(|| format!("no import found at {path}:{line}"))?;
+
+    let target = if import.target.starts_with("https://") || import.target.starts_with("http://") {
+        docs::fetch_text(&import.target)?
+    } else if let Some(local) = imports::resolve_local(path, &import.target) {
+        worktree.read_text_file(&local)?
+    } else {
+        return Err(format!("cannot read {}", import.target));
+    };
+
+    let entry = Entry::parse(&import.target, &target);
+    let mut text = entry.to_markdown();
+    if entry.description.is_none() {
+        text.push_str("This file has no documentation comment.\n");
+    }
+    if let Some(hash) = import.hash {
+        text.push_str(&format!("\nPinned to `{}`.\n", &source[hash]));
+    }
+    let fields = docs::package_entries(&target);
+    if !fields.is_empty() {
+        text.push_str("\n## Fields\n\n");
+        for (name, field_target) in fields {
+            text.push_str(&format!("- `{name}` (`{field_target}`)\n"));
+        }
+    }
+
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: format!("Docs for {}", import.target),
+        }],
+        text,
+    })
+}
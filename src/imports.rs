@@ -0,0 +1,136 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+
+use crate::syntax::{self, TokenKind};
+
+#[derive(Debug, Clone)]
+pub struct Import {
+    /// The path, URL or environment variable, as written.
+    pub target: String,
+    /// The `sha256:…` integrity check, if any.
+    pub hash: Option<Range<usize>>,
+    /// The whole import, including any integrity check and `as` clause.
+    pub span: Range<usize>,
+}
+
+pub fn imports(source: &str) -> Vec<Import> {
+    let tokens = syntax::code_tokens(source);
+    let mut imports = Vec::new();
+    let mut ix = 0;
+    while ix < tokens.len() {
+        let token = &tokens[ix];
+        if !matches!(
+            token.kind,
+            TokenKind::Path | TokenKind::Url | TokenKind::Env
+        ) {
+            ix += 1;
+            continue;
+        }
+        let start = ix;
+        ix += 1;
+
+        if token.kind == TokenKind::Url && tokens.get(ix).is_some_and(|t| t.is(source, "using")) {
+            ix += 1;
+            if tokens.get(ix).is_some_and(|t| t.kind == TokenKind::Open) {
+                ix = syntax::matching_close(&tokens, ix);
+            }
+            ix = (ix + 1).min(tokens.len());
+        }
+
+        let mut hash = None;
+        if let Some(token) = tokens.get(ix).filter(|t| t.kind == TokenKind::Hash) {
+            hash = Some(token.span.clone());
+            ix += 1;
+        }
+        if tokens.get(ix).is_some_and(|t| t.is(source, "as")) {
+            ix = (ix + 2).min(tokens.len());
+        }
+
+        imports.push(Import {
+            target: token.text(source).to_string(),
+            hash,
+            span: syntax::span_between(&tokens, start, ix),
+        });
+    }
+    imports
+}
+
+/// Replaces the target of each import for which `rewrite` returns a new path.
+pub fn rewrite_imports(
+    source: &str,
+    rewrite: impl Fn(&Import) -> Option<String>,
+) -> Option<String> {
+    let mut output = String::with_capacity(source.len());
+    let mut last = 0;
+    for import in imports(source) {
+        if let Some(target) = rewrite(&import) {
+            output.push_str(&source[last..import.span.start]);
+            output.push_str(&target);
+            last = import.span.start + import.target.len();
+        }
+    }
+    if last == 0 {
+        return None;
+    }
+    output.push_str(&source[last..]);
+    Some(output)
+}
+
+/// Returns the relative import that refers to `target` from within `importer`,
+/// where both paths are relative to the worktree root.
+pub fn relative_import(importer: &str, target: &str) -> String {
+    let from: Vec<Component> = Path::new(importer)
+        .parent()
+        .map(|dir| dir.components().collect())
+        .unwrap_or_default();
+    let to: Vec<Component> = Path::new(target).components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut path = PathBuf::new();
+    for _ in common..from.len() {
+        path.push("..");
+    }
+    path.extend(&to[common..]);
+    let path = path.to_string_lossy().replace('\\', "/");
+    if path.starts_with("..") {
+        path
+    } else {
+        format!("./{path}")
+    }
+}
+
+/// Resolves a relative import written in `importer` to a path relative to
+/// the worktree root. Absolute and home-relative paths aren't resolved.
+pub fn resolve_local(importer: &str, target: &str) -> Option<String> {
+    if !(target.starts_with("./") || target.starts_with("../")) {
+        return None;
+    }
+    let mut path: Vec<&str> = importer.split('/').collect();
+    path.pop();
+    for segment in target.split('/') {
+        match segment {
+            "." | "" => {}
+            ".." => {
+                path.pop()?;
+            }
+            segment => path.push(segment),
+        }
+    }
+    Some(path.join("/"))
+}
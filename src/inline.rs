@@ -0,0 +1,87 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::path::{Path, PathBuf};
+
+use zed_extension_api::{self as zed, Result, SlashCommandOutput, SlashCommandOutputSection};
+
+use crate::{cli, imports, location};
+
+pub const SLASH_COMMAND: &str = "dhall-inline";
+
+/// Inlined imports larger than this are flagged in the output.
+const LARGE_IMPORT_BYTES: usize = 64 * 1024;
+
+pub fn run_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let (path, line) = location::parse(
+        args.first()
+            .ok_or_else(|| "usage: /dhall-inline <file>:<line>".to_string())?,
+    )?;
+    let source = worktree.read_text_file(path)?;
+    let line_span = location::line_span(&source, line);
+    let import = imports::imports(&source)
+        .into_iter()
+        .find(|import| line_span.contains(&import.span.start))
+        .ok_or_else(|| format!("no import found at {path}:{line}"))?;
+
+    let dhall = cli::which(worktree, "dhall")?;
+    let root = PathBuf::from(worktree.root_path());
+    let cwd = root
+        .join(path)
+        .parent()
+        .map_or(root.clone(), Path::to_path_buf);
+    let output = cli::run(
+        &dhall,
+        &["resolve"],
+        &cwd,
+        Some(&source[import.span.clone()]),
+    )?;
+    if !output.success {
+        return Err(format!(
+            "failed to resolve {}: {}",
+            import.target,
+            output.stderr.trim()
+        ));
+    }
+    let resolved = output.stdout.trim();
+
+    let mut text = String::new();
+    let mut sections = Vec::new();
+    if resolved.len() > LARGE_IMPORT_BYTES {
+        text.push_str(&format!(
+            "Warning: {} resolves to {} KiB of Dhall source.\n\n",
+            import.target,
+            resolved.len() / 1024
+        ));
+        sections.push(SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: "Large import".to_string(),
+        });
+    }
+    let start = text.len();
+    text.push_str(&source[..import.span.start]);
+    text.push_str(&format!("( {resolved}\n)"));
+    text.push_str(&source[import.span.end..]);
+    sections.push(SlashCommandOutputSection {
+        range: (start..text.len()).into(),
+        label: format!("{path} with {} inlined", import.target),
+    });
+
+    Ok(SlashCommandOutput { text, sections })
+}
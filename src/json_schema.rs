@@ -0,0 +1,623 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::path::PathBuf;
+
+use zed_extension_api::{
+    self as zed,
+    serde_json::{self, json, Map, Value},
+    Result, SlashCommandOutput, SlashCommandOutputSection,
+};
+
+use crate::syntax::{self, Token, TokenKind};
+use crate::{builtins, cli};
+
+pub const SLASH_COMMAND: &str = "dhall-json-schema";
+pub const FROM_JSON_SCHEMA_SLASH_COMMAND: &str = "dhall-from-json-schema";
+
+/// The type of arbitrary JSON, used where a schema doesn't constrain values.
+const JSON_TYPE: &str = "https://prelude.dhall-lang.org/JSON/Type";
+
+/// Translates a normalized Dhall type into the JSON Schema of the values
+/// `dhall-to-json` renders for it.
+struct Translator<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl<'a> Translator<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            tokens: syntax::code_tokens(source),
+            pos: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens
+            .get(self.pos)
+            .map(|token| token.text(self.source))
+    }
+
+    fn next(&mut self) -> Result<&'a str> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .ok_or_else(|| "unexpected end of type".to_string())?;
+        self.pos += 1;
+        Ok(token.text(self.source))
+    }
+
+    fn expect(&mut self, text: &str) -> Result<()> {
+        match self.next()? {
+            found if found == text => Ok(()),
+            found => Err(format!("expected `{text}`, found `{found}`")),
+        }
+    }
+
+    fn translate(mut self) -> Result<Value> {
+        let schema = self.schema()?;
+        match self.peek() {
+            None => Ok(schema),
+            Some(rest) => Err(format!("cannot translate a type containing `{rest}`")),
+        }
+    }
+
+    /// Returns the schema of a type, and whether the type was `Optional`.
+    fn optional_schema(&mut self) -> Result<(Value, bool)> {
+        let token = self.next()?;
+        let (schema, optional) = match token {
+            "Text" => (json!({ "type": "string" }), false),
+            "Natural" => (json!({ "type": "integer", "minimum": 0 }), false),
+            "Integer" => (json!({ "type": "integer" }), false),
+            "Double" => (json!({ "type": "number" }), false),
+            "Bool" => (json!({ "type": "boolean" }), false),
+            "Optional" => (self.schema()?, true),
+            "List" => (self.list()?, false),
+            "{" => (self.record()?, false),
+            "<" => (self.union()?, false),
+            "(" => {
+                let inner = self.optional_schema()?;
+                self.expect(")")?;
+                inner
+            }
+            token => return Err(format!("cannot translate `{token}` to JSON Schema")),
+        };
+        if matches!(self.peek(), Some("->" | "→")) {
+            return Err("functions cannot be rendered as JSON".to_string());
+        }
+        Ok((schema, optional))
+    }
+
+    fn schema(&mut self) -> Result<Value> {
+        let (schema, optional) = self.optional_schema()?;
+        Ok(if optional {
+            json!({ "anyOf": [schema, { "type": "null" }] })
+        } else {
+            schema
+        })
+    }
+
+    /// `List { mapKey : Text, mapValue : T }` is rendered as an object.
+    fn list(&mut self) -> Result<Value> {
+        let start = self.pos;
+        if self.peek() == Some("{") {
+            self.pos += 1;
+            let fields = self.fields()?;
+            if let [(key, key_type, false), (value, value_type, optional)] = fields.as_slice() {
+                if key == "mapKey" && value == "mapValue" && key_type["type"] == "string" {
+                    let value_type = if *optional {
+                        json!({ "anyOf": [value_type, { "type": "null" }] })
+                    } else {
+                        value_type.clone()
+                    };
+                    return Ok(json!({ "type": "object", "additionalProperties": value_type }));
+                }
+            }
+            self.pos = start;
+        }
+        Ok(json!({ "type": "array", "items": self.schema()? }))
+    }
+
+    /// Parses record fields up to and including the closing `}`.
+    fn fields(&mut self) -> Result<Vec<(String, Value, bool)>> {
+        let mut fields = Vec::new();
+        while self.peek() != Some("}") {
+            let name = self.next()?.trim_matches('`').to_string();
+            self.expect(":")?;
+            let (schema, optional) = self.optional_schema()?;
+            fields.push((name, schema, optional));
+            if self.peek() == Some(",") {
+                self.pos += 1;
+            }
+        }
+        self.expect("}")?;
+        Ok(fields)
+    }
+
+    fn record(&mut self) -> Result<Value> {
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        for (name, schema, optional) in self.fields()? {
+            if !optional {
+                required.push(Value::String(name.clone()));
+            }
+            properties.insert(name, schema);
+        }
+        Ok(json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false,
+        }))
+    }
+
+    /// Unions are rendered as their payload, or as the alternative's name
+    /// when it has none.
+    fn union(&mut self) -> Result<Value> {
+        let mut names = Vec::new();
+        let mut payloads = Vec::new();
+        while self.peek() != Some(">") {
+            let name = self.next()?.trim_matches('`').to_string();
+            if self.peek() == Some(":") {
+                self.pos += 1;
+                payloads.push(self.schema()?);
+            } else {
+                names.push(Value::String(name));
+            }
+            if self.peek() == Some("|") {
+                self.pos += 1;
+            }
+        }
+        self.expect(">")?;
+        if payloads.is_empty() {
+            return Ok(json!({ "type": "string", "enum": names }));
+        }
+        if !names.is_empty() {
+            payloads.push(json!({ "type": "string", "enum": names }));
+        }
+        Ok(json!({ "anyOf": payloads }))
+    }
+}
+
+pub fn to_json_schema(dhall_type: &str) -> Result<Value> {
+    let translator = Translator::new(dhall_type);
+    if let Some(token) = translator.tokens.iter().find(|t| t.kind == TokenKind::Url) {
+        return Err(format!("unresolved import {}", token.text(dhall_type)));
+    }
+    let mut schema = translator.translate()?;
+    if let Value::Object(object) = &mut schema {
+        object.insert(
+            "$schema".to_string(),
+            json!("https://json-schema.org/draft/2020-12/schema"),
+        );
+    }
+    Ok(schema)
+}
+
+/// Exports the type defined by a Dhall file as a JSON Schema document.
+pub fn run_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let path = args
+        .first()
+        .ok_or_else(|| "usage: /dhall-json-schema <file>".to_string())?;
+    let dhall = cli::which(worktree, "dhall")?;
+    let import = format!("./{}", path.trim_start_matches("./"));
+    let output = cli::run(
+        &dhall,
+        &["--ascii"],
+        &PathBuf::from(worktree.root_path()),
+        Some(&import),
+    )?;
+    if !output.success {
+        return Err(format!(
+            "failed to normalize {path}: {}",
+            output.stderr.trim()
+        ));
+    }
+
+    let schema = to_json_schema(&output.stdout)?;
+    let text = serde_json::to_string_pretty(&schema).map_err(|e| e.to_string())? + "\n";
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: format!("JSON Schema for {path}"),
+        }],
+        text,
+    })
+}
+
+/// Builds Dhall types from a JSON Schema document, binding each entry of
+/// `$defs` (or `definitions`) with a `let`.
+struct TypeBuilder<'a> {
+    definitions: Option<&'a Map<String, Value>>,
+    /// Definitions already emitted, in dependency order.
+    bindings: Vec<(String, String)>,
+    /// Definitions being translated, to reject recursive schemas.
+    visiting: Vec<String>,
+    uses_json: bool,
+}
+
+fn label(name: &str) -> String {
+    let is_simple = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '/'))
+        && builtins::lookup_keyword(name).is_none()
+        && builtins::lookup(name).is_none();
+    if is_simple {
+        name.to_string()
+    } else {
+        format!("`{name}`")
+    }
+}
+
+impl<'a> TypeBuilder<'a> {
+    fn dhall_type(&mut self, schema: &'a Value) -> Result<String> {
+        let Some(object) = schema.as_object() else {
+            // `true` accepts anything.
+            self.uses_json = true;
+            return Ok("JSON".to_string());
+        };
+
+        if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+            return self.reference(reference);
+        }
+        if let Some(values) = object.get("enum").and_then(Value::as_array) {
+            let labels: Option<Vec<String>> = values
+                .iter()
+                .map(|value| value.as_str().map(label))
+                .collect();
+            return labels
+                .map(|labels| format!("< {} >", labels.join(" | ")))
+                .ok_or_else(|| "only enums of strings can be translated".to_string());
+        }
+        if let Some(alternatives) = object
+            .get("anyOf")
+            .or_else(|| object.get("oneOf"))
+            .and_then(Value::as_array)
+        {
+            return self.alternatives(alternatives);
+        }
+
+        match object.get("type") {
+            Some(Value::String(kind)) => self.simple_type(kind, object),
+            Some(Value::Array(kinds)) => {
+                let kinds: Vec<&str> = kinds.iter().filter_map(Value::as_str).collect();
+                match kinds.as_slice() {
+                    [kind, "null"] | ["null", kind] => Ok(format!(
+                        "Optional {}",
+                        parenthesize(self.simple_type(kind, object)?)
+                    )),
+                    _ => Err(format!("cannot translate a value of types {kinds:?}")),
+                }
+            }
+            _ if object.contains_key("properties") => self.simple_type("object", object),
+            _ => {
+                self.uses_json = true;
+                Ok("JSON".to_string())
+            }
+        }
+    }
+
+    fn simple_type(&mut self, kind: &str, object: &'a Map<String, Value>) -> Result<String> {
+        match kind {
+            "string" => Ok("Text".to_string()),
+            "boolean" => Ok("Bool".to_string()),
+            "number" => Ok("Double".to_string()),
+            "integer" => {
+                let minimum = object.get("minimum").and_then(Value::as_f64);
+                Ok(if minimum.is_some_and(|minimum| minimum >= 0.0) {
+                    "Natural".to_string()
+                } else {
+                    "Integer".to_string()
+                })
+            }
+            "array" => {
+                let items = match object.get("items") {
+                    Some(items) => self.dhall_type(items)?,
+                    None => {
+                        self.uses_json = true;
+                        "JSON".to_string()
+                    }
+                };
+                Ok(format!("List {}", parenthesize(items)))
+            }
+            "object" => self.record(object),
+            "null" => Err("a value that is always null has no Dhall type".to_string()),
+            kind => Err(format!("unknown JSON Schema type {kind}")),
+        }
+    }
+
+    fn record(&mut self, object: &'a Map<String, Value>) -> Result<String> {
+        let properties = object.get("properties").and_then(Value::as_object);
+        if properties.is_none_or(Map::is_empty) {
+            return match object.get("additionalProperties") {
+                Some(Value::Object(_)) => {
+                    let value = self.dhall_type(&object["additionalProperties"])?;
+                    Ok(format!("List {{ mapKey : Text, mapValue : {value} }}"))
+                }
+                Some(Value::Bool(false)) => Ok("{}".to_string()),
+                _ => {
+                    self.uses_json = true;
+                    Ok("List { mapKey : Text, mapValue : JSON }".to_string())
+                }
+            };
+        }
+
+        let required: Vec<&str> = object
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|required| required.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        let mut fields = Vec::new();
+        for (name, schema) in properties.into_iter().flatten() {
+            let field_type = self.dhall_type(schema)?;
+            let field_type =
+                if required.contains(&name.as_str()) || field_type.starts_with("Optional ") {
+                    field_type
+                } else {
+                    format!("Optional {}", parenthesize(field_type))
+                };
+            fields.push(format!("{} : {field_type}", label(name)));
+        }
+        Ok(format!("{{ {} }}", fields.join(", ")))
+    }
+
+    fn alternatives(&mut self, alternatives: &'a [Value]) -> Result<String> {
+        let is_null = |schema: &Value| schema.get("type").and_then(Value::as_str) == Some("null");
+        let non_null: Vec<&Value> = alternatives
+            .iter()
+            .filter(|schema| !is_null(schema))
+            .collect();
+        let nullable = non_null.len() < alternatives.len();
+
+        let dhall_type = match non_null.as_slice() {
+            [] => return Err("a value that is always null has no Dhall type".to_string()),
+            [schema] => self.dhall_type(schema)?,
+            schemas => {
+                let mut labels = Vec::new();
+                for (ix, schema) in schemas.iter().enumerate() {
+                    let name = schema
+                        .get("title")
+                        .and_then(Value::as_str)
+                        .map(str::to_string)
+                        .or_else(|| {
+                            let kind = schema.get("type")?.as_str()?;
+                            Some(kind[..1].to_uppercase() + &kind[1..])
+                        })
+                        .unwrap_or_else(|| format!("Alternative{}", ix + 1));
+                    labels.push(format!("{} : {}", label(&name), self.dhall_type(schema)?));
+                }
+                format!("< {} >", labels.join(" | "))
+            }
+        };
+        Ok(if nullable {
+            format!("Optional {}", parenthesize(dhall_type))
+        } else {
+            dhall_type
+        })
+    }
+
+    fn reference(&mut self, reference: &str) -> Result<String> {
+        let name = ["#/$defs/", "#/definitions/"]
+            .iter()
+            .find_map(|prefix| reference.strip_prefix(prefix))
+            .ok_or_else(|| format!("cannot follow $ref {reference}"))?;
+        let binding = label(name);
+        if self.bindings.iter().any(|(bound, _)| *bound == binding) {
+            return Ok(binding);
+        }
+        if self.visiting.iter().any(|visiting| visiting == name) {
+            return Err(format!(
+                "{name} is recursive, which Dhall types cannot express"
+            ));
+        }
+        let schema = self
+            .definitions
+            .and_then(|definitions| definitions.get(name))
+            .ok_or_else(|| format!("{reference} is not defined"))?;
+        self.visiting.push(name.to_string());
+        let dhall_type = self.dhall_type(schema)?;
+        self.visiting.pop();
+        self.bindings.push((binding.clone(), dhall_type));
+        Ok(binding)
+    }
+}
+
+fn parenthesize(dhall_type: String) -> String {
+    if dhall_type.contains(' ') && !dhall_type.starts_with(['{', '<', '(']) {
+        format!("({dhall_type})")
+    } else {
+        dhall_type
+    }
+}
+
+/// Translates a JSON Schema document into a Dhall type expression.
+pub fn from_json_schema(schema: &Value) -> Result<String> {
+    let mut builder = TypeBuilder {
+        definitions: schema
+            .get("$defs")
+            .or_else(|| schema.get("definitions"))
+            .and_then(Value::as_object),
+        bindings: Vec::new(),
+        visiting: Vec::new(),
+        uses_json: false,
+    };
+    let root = builder.dhall_type(schema)?;
+
+    let mut source = String::new();
+    if builder.uses_json {
+        source.push_str(&format!("let JSON = {JSON_TYPE}\n\n"));
+    }
+    for (name, dhall_type) in &builder.bindings {
+        source.push_str(&format!("let {name} = {dhall_type}\n\n"));
+    }
+    if source.is_empty() {
+        source = root;
+    } else {
+        source.push_str(&format!("in  {root}"));
+    }
+    source.push('\n');
+    Ok(source)
+}
+
+/// Converts a JSON Schema file into Dhall types, formatted with `dhall
+/// format` when it is available.
+pub fn run_from_json_schema_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let path = args
+        .first()
+        .ok_or_else(|| "usage: /dhall-from-json-schema <schema.json>".to_string())?;
+    let schema: Value = serde_json::from_str(&worktree.read_text_file(path)?)
+        .map_err(|e| format!("{path} is not valid JSON: {e}"))?;
+    let mut text = from_json_schema(&schema)?;
+
+    if let Ok(dhall) = cli::which(worktree, "dhall") {
+        let root = PathBuf::from(worktree.root_path());
+        if let Ok(output) = cli::run(&dhall, &["format"], &root, Some(&text)) {
+            if output.success {
+                text = output.stdout;
+            }
+        }
+    }
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: format!("Dhall types for {path}"),
+        }],
+        text,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translates_records_with_optional_fields() {
+        let schema =
+            to_json_schema("{ name : Text, age : Optional Natural, tags : List Text }").unwrap();
+        assert_eq!(
+            schema,
+            json!({
+                "$schema": "https://json-schema.org/draft/2020-12/schema",
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "age": { "type": "integer", "minimum": 0 },
+                    "tags": { "type": "array", "items": { "type": "string" } },
+                },
+                "required": ["name", "tags"],
+                "additionalProperties": false,
+            })
+        );
+    }
+
+    #[test]
+    fn translates_scalars() {
+        for (dhall_type, json_type) in [
+            ("Bool", "boolean"),
+            ("Double", "number"),
+            ("Integer", "integer"),
+            ("Text", "string"),
+        ] {
+            assert_eq!(
+                to_json_schema(dhall_type).unwrap()["type"],
+                json!(json_type)
+            );
+        }
+    }
+
+    #[test]
+    fn translates_unions_to_payloads_and_names() {
+        let schema = to_json_schema("< A | B : Natural >").unwrap();
+        assert_eq!(
+            schema["anyOf"],
+            json!([
+                { "type": "integer", "minimum": 0 },
+                { "type": "string", "enum": ["A"] },
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_unresolved_imports() {
+        assert!(to_json_schema("https://example.com/T.dhall").is_err());
+    }
+
+    fn from_json(schema: &str) -> Result<String> {
+        from_json_schema(&serde_json::from_str(schema).unwrap())
+    }
+
+    #[test]
+    fn builds_records_with_optional_fields() {
+        let schema = r#"{
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "n": { "type": "integer", "minimum": 0 }
+            },
+            "required": ["name"]
+        }"#;
+        assert_eq!(
+            from_json(schema).unwrap(),
+            "{ n : Optional Natural, name : Text }\n"
+        );
+    }
+
+    #[test]
+    fn binds_definitions_and_enums() {
+        let schema = r##"{
+            "$defs": {
+                "Pet": {
+                    "type": "object",
+                    "properties": { "kind": { "enum": ["cat", "dog"] } },
+                    "required": ["kind"]
+                }
+            },
+            "type": "array",
+            "items": { "$ref": "#/$defs/Pet" }
+        }"##;
+        assert_eq!(
+            from_json(schema).unwrap(),
+            "let Pet = { kind : < cat | dog > }\n\nin  List Pet\n"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_json_for_unconstrained_values() {
+        assert_eq!(
+            from_json("{}").unwrap(),
+            format!("let JSON = {JSON_TYPE}\n\nin  JSON\n")
+        );
+    }
+
+    #[test]
+    fn rejects_recursive_schemas() {
+        let schema = r##"{
+            "$defs": { "T": { "type": "object", "properties": { "c": { "$ref": "#/$defs/T" } } } },
+            "$ref": "#/$defs/T"
+        }"##;
+        assert!(from_json(schema).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use zed_extension_api::{
+    lsp::{Completion, CompletionKind, Symbol, SymbolKind},
+    CodeLabel, CodeLabelSpan,
+};
+
+use crate::docs::Entry;
+use crate::{builtins, type_search};
+
+pub fn completion_label(completion: Completion, prelude: &[Entry]) -> Option<CodeLabel> {
+    if let Some(keyword) = builtins::lookup_keyword(&completion.label) {
+        return Some(keyword_label(keyword));
+    }
+
+    let builtin = builtins::lookup(&completion.label);
+    let server_detail = completion
+        .detail
+        .as_deref()
+        .or_else(|| completion.label_details.as_ref()?.detail.as_deref())
+        .map(str::trim)
+        .filter(|detail| !detail.is_empty())
+        .map(|detail| detail.strip_prefix(':').unwrap_or(detail).trim_start());
+    let prelude_entry = prelude_entry(&completion.label, server_detail, prelude);
+    let detail = server_detail
+        .or(builtin.map(|builtin| builtin.signature))
+        .or(prelude_entry.and_then(|entry| entry.signature.as_deref()))?;
+
+    if let Some(payload) = union_alternative(&completion, detail) {
+        return Some(union_alternative_label(&completion.label, payload));
+    }
+
+    // `name : type` is itself a valid Dhall expression, so the whole label
+    // can be highlighted by the grammar.
+    let code = format!("{} : {detail}", completion.label);
+    let mut spans = vec![CodeLabelSpan::code_range(0..code.len())];
+    let doc = builtin.map(|builtin| builtin.doc).or_else(|| {
+        let description = prelude_entry?.description.as_deref()?;
+        description.lines().next().filter(|line| !line.is_empty())
+    });
+    if let Some(doc) = doc {
+        spans.push(CodeLabelSpan::literal(
+            format!("  -- {doc}"),
+            Some("comment".to_string()),
+        ));
+    }
+    Some(CodeLabel {
+        spans,
+        filter_range: (0..completion.label.len()).into(),
+        code,
+    })
+}
+
+/// Finds the Prelude function a completion refers to, e.g. `List/map` for a
+/// `map` field of `Prelude.List`. Several modules share field names, so when
+/// the server gave a type it must match, and otherwise the name must be
+/// unambiguous.
+fn prelude_entry<'a>(label: &str, detail: Option<&str>, prelude: &'a [Entry]) -> Option<&'a Entry> {
+    let mut candidates = prelude
+        .iter()
+        .filter(|entry| entry.signature.is_some() && entry.name.rsplit('/').next() == Some(label));
+    match detail {
+        Some(detail) => {
+            let detail = type_search::normalize(detail);
+            candidates.find(|entry| {
+                entry
+                    .signature
+                    .as_deref()
+                    .is_some_and(|signature| type_search::normalize(signature) == detail)
+            })
+        }
+        None => {
+            let entry = candidates.next()?;
+            candidates.next().is_none().then_some(entry)
+        }
+    }
+}
+
+/// Labels a keyword with its name, which is what the completion is filtered
+/// on, followed by its syntax (`forall` is written `∀`, and a few keywords
+/// are only part of a larger expression).
+fn keyword_label(keyword: &builtins::Keyword) -> CodeLabel {
+    let code = keyword.syntax.to_string();
+    let mut spans = vec![CodeLabelSpan::literal(
+        keyword.name,
+        Some("keyword".to_string()),
+    )];
+    if keyword.syntax != keyword.name {
+        spans.push(CodeLabelSpan::literal("  ", None));
+        spans.push(CodeLabelSpan::code_range(0..code.len()));
+    }
+    spans.push(CodeLabelSpan::literal(
+        format!("  -- {}", keyword.doc),
+        Some("comment".to_string()),
+    ));
+    CodeLabel {
+        spans,
+        filter_range: (0..keyword.name.len()).into(),
+        code,
+    }
+}
+
+/// Recognizes a union constructor, returning its payload type (if any).
+///
+/// The server reports a constructor's type as a function from the payload
+/// to the union, e.g. `∀(Ok : Natural) → < Error : Text | Ok : Natural >`,
+/// and a nullary constructor's type as the union itself.
+fn union_alternative<'a>(completion: &Completion, detail: &'a str) -> Option<Option<&'a str>> {
+    let binder = ["∀(", "forall(", "forall ("]
+        .iter()
+        .find_map(|prefix| detail.strip_prefix(prefix))
+        .and_then(|rest| rest.trim_start().strip_prefix(completion.label.as_str()))
+        .and_then(|rest| rest.trim_start().strip_prefix(':'));
+    if let Some(rest) = binder {
+        let (payload, union) = split_binder(rest)?;
+        if union.starts_with('<') {
+            return Some(Some(payload));
+        }
+    }
+
+    let is_constructor = matches!(
+        completion.kind,
+        Some(CompletionKind::EnumMember | CompletionKind::Constructor)
+    );
+    // A variable or field whose type is a union isn't an alternative of it,
+    // unless the server says so or the union has an alternative by its name.
+    if detail.starts_with('<') && (is_constructor || has_alternative(detail, &completion.label)) {
+        Some(None)
+    } else if is_constructor {
+        Some(Some(detail))
+    } else {
+        None
+    }
+}
+
+/// Whether the union type `union` has a nullary alternative named `label`.
+fn has_alternative(union: &str, label: &str) -> bool {
+    let Some(body) = union
+        .trim()
+        .strip_prefix('<')
+        .and_then(|body| body.strip_suffix('>'))
+    else {
+        return false;
+    };
+    let mut depth = 0usize;
+    let mut start = 0;
+    let mut alternatives = Vec::new();
+    let mut previous = ' ';
+    for (ix, c) in body.char_indices() {
+        match c {
+            '(' | '{' | '[' | '<' => depth += 1,
+            // The `>` of an ASCII arrow doesn't close anything.
+            '>' if previous == '-' => {}
+            ')' | '}' | ']' | '>' => depth = depth.saturating_sub(1),
+            '|' if depth == 0 => {
+                alternatives.push(&body[start..ix]);
+                start = ix + 1;
+            }
+            _ => {}
+        }
+        previous = c;
+    }
+    alternatives.push(&body[start..]);
+    alternatives
+        .iter()
+        .any(|alternative| alternative.trim().trim_matches('`') == label)
+}
+
+/// Splits `T) → U` at the closing parenthesis of a `∀` binder.
+fn split_binder(rest: &str) -> Option<(&str, &str)> {
+    let mut depth = 0usize;
+    for (ix, c) in rest.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => {
+                let body = rest[ix + 1..].trim_start();
+                let body = ["→", "->"]
+                    .iter()
+                    .find_map(|arrow| body.strip_prefix(arrow))?;
+                return Some((rest[..ix].trim(), body.trim_start()));
+            }
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn union_alternative_label(label: &str, payload: Option<&str>) -> CodeLabel {
+    let code = match payload {
+        Some(payload) => format!("< {label} : {payload} >"),
+        None => format!("< {label} >"),
+    };
+    CodeLabel {
+        spans: vec![CodeLabelSpan::code_range(0..code.len())],
+        filter_range: (2..2 + label.len()).into(),
+        code,
+    }
+}
+
+pub fn symbol_label(symbol: Symbol) -> Option<CodeLabel> {
+    let shape = match symbol.kind {
+        SymbolKind::Struct | SymbolKind::Object | SymbolKind::Class | SymbolKind::Interface => {
+            "{ … }"
+        }
+        SymbolKind::Enum => "< … >",
+        SymbolKind::Function | SymbolKind::Method | SymbolKind::Constructor => "λ(…) → …",
+        SymbolKind::Variable | SymbolKind::Constant => "…",
+        _ => return None,
+    };
+
+    // Only the binding name is parsed; the elided value is a literal so a
+    // partial expression doesn't confuse the highlighter.
+    let code = format!("let {} = x", symbol.name);
+    let name = 4..4 + symbol.name.len();
+    Some(CodeLabel {
+        spans: vec![
+            CodeLabelSpan::code_range(name),
+            CodeLabelSpan::literal(" = ", None),
+            CodeLabelSpan::literal(shape, Some("punctuation.bracket".to_string())),
+        ],
+        filter_range: (0..symbol.name.len()).into(),
+        code,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_nullary_alternatives() {
+        let union = "< Error : Text | Ok | `Not found` >";
+        assert!(has_alternative(union, "Ok"));
+        assert!(has_alternative(union, "Not found"));
+        // `Error` has a payload, so it isn't nullary.
+        assert!(!has_alternative(union, "Error"));
+        assert!(!has_alternative(union, "Missing"));
+    }
+
+    #[test]
+    fn skips_nested_types_and_arrows() {
+        let union = "< F : Natural -> Text | G : < A | B > | C >";
+        assert!(has_alternative(union, "C"));
+        assert!(!has_alternative(union, "A"));
+        assert!(!has_alternative("{ Ok : Bool }", "Ok"));
+    }
+}
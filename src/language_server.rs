@@ -14,32 +14,173 @@
    limitations under the License.
 */
 
+use std::env;
 use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
 
-use zed_extension_api::{self as zed, LanguageServerId, Result};
+use serde::{Deserialize, Serialize};
+use zed_extension_api::{
+    self as zed, serde_json,
+    settings::{CommandSettings, LspSettings},
+    LanguageServerId, Result,
+};
+
+use crate::settings::DhallSettings;
+use crate::{cli, toolchain};
+
+/// Runs the language server for formatting only, on Zed's Node.js runtime.
+const FILTER_SCRIPT: &str = include_str!("../language-server/filter.js");
+const SCRIPT_DIR: &str = "language-server";
+
+/// An environment variable naming the language server to run.
+const SERVER_ENV_VAR: &str = "DHALL_LSP_SERVER";
+
+/// Where the installed language server was found, kept across sessions so
+/// that it starts without searching the working directory again.
+const INSTALL_STATE: &str = "install-state.json";
+
+/// How often to check for a newer release of the language server.
+const UPDATE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Deserialize, Serialize)]
+struct InstallState {
+    binary_path: String,
+    /// The binary's size, to tell that it is the file that was installed.
+    size: u64,
+    /// When a newer release was last looked for, in seconds since the epoch.
+    #[serde(default)]
+    checked_at: u64,
+    /// The newer release found then, installed the next time it starts.
+    #[serde(default)]
+    update_available: Option<String>,
+}
+
+impl InstallState {
+    fn load() -> Option<Self> {
+        let json = fs::read_to_string(INSTALL_STATE).ok()?;
+        let state: Self = serde_json::from_str(&json).ok()?;
+        fs::metadata(&state.binary_path)
+            .is_ok_and(|stat| stat.is_file() && stat.len() == state.size)
+            .then_some(state)
+    }
+
+    fn save(binary_path: &str, checked_at: u64, update_available: Option<&str>) {
+        let Ok(stat) = fs::metadata(binary_path) else {
+            return;
+        };
+        let state = Self {
+            binary_path: binary_path.to_string(),
+            size: stat.len(),
+            checked_at,
+            update_available: update_available.map(str::to_string),
+        };
+        if let Ok(json) = serde_json::to_string(&state) {
+            fs::write(INSTALL_STATE, json).ok();
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
 
 pub struct DhallLanguageServer {
     cached_binary_path: Option<String>,
+    checked_at: u64,
+    /// Whether the server was started from an installed release that is due
+    /// a check for a newer one.
+    update_due: bool,
+    /// A newer release than the running server's, found by
+    /// `check_for_update`.
+    update_available: Option<String>,
 }
 
 impl DhallLanguageServer {
     pub const LANGUAGE_SERVER_ID: &'static str = "dhall";
 
     pub fn new() -> Self {
+        let state = InstallState::load();
         Self {
-            cached_binary_path: None,
+            checked_at: state.as_ref().map_or(0, |state| state.checked_at),
+            update_available: state
+                .as_ref()
+                .and_then(|state| state.update_available.clone()),
+            cached_binary_path: state.map(|state| state.binary_path),
+            update_due: false,
+        }
+    }
+
+    fn save_state(&self) {
+        if let Some(path) = &self.cached_binary_path {
+            InstallState::save(path, self.checked_at, self.update_available.as_deref());
         }
     }
 
+    pub fn cached_binary_path(&self) -> Option<&str> {
+        self.cached_binary_path.as_deref()
+    }
+
     pub fn language_server_command(
         &mut self,
         language_server_id: &LanguageServerId,
         worktree: &zed::Worktree,
     ) -> Result<zed::Command> {
+        let binary = LspSettings::for_worktree(Self::LANGUAGE_SERVER_ID, worktree)?.binary;
+        let settings = DhallSettings::for_worktree(worktree)?;
+        let mut command = match binary.filter(|binary| binary.path.is_some()) {
+            Some(binary) => Self::configured_command(binary, worktree)?,
+            None => zed::Command {
+                command: self.language_server_binary_path(language_server_id, worktree)?,
+                args: Vec::new(),
+                env: Default::default(),
+            },
+        };
+        if let Some(file) = settings.trace_file {
+            // `--log` is `dhall-lsp-server`'s own flag; another configured
+            // server may not accept it.
+            let is_lsp_server = Path::new(&command.command)
+                .file_stem()
+                .is_some_and(|stem| stem == "dhall-lsp-server");
+            if !is_lsp_server {
+                return Err(format!(
+                    "invalid lsp.dhall.settings: trace_file needs dhall-lsp-server, not {}",
+                    command.command
+                ));
+            }
+            let file = Path::new(&worktree.root_path()).join(file);
+            command
+                .args
+                .extend(["--log".to_string(), file.to_string_lossy().into_owned()]);
+        }
+        if let Some(megabytes) = settings.max_memory_mb {
+            // Caps the Haskell runtime's heap, so that the server fails
+            // with a heap overflow instead of the machine swapping. Every
+            // build of the server, `dhall lsp` included, is a Haskell
+            // program.
+            command.args.extend([
+                "+RTS".to_string(),
+                format!("-M{megabytes}m"),
+                "-RTS".to_string(),
+            ]);
+        }
+        Ok(command)
+    }
+
+    /// Runs the server configured in `lsp.dhall.binary`, such as `dhall`
+    /// with the arguments `["lsp"]` where only the unified binary is
+    /// packaged. A bare name is looked up on the `PATH`.
+    fn configured_command(
+        binary: CommandSettings,
+        worktree: &zed::Worktree,
+    ) -> Result<zed::Command> {
+        let path = binary.path.unwrap_or_default();
         Ok(zed::Command {
-            command: self.language_server_binary_path(language_server_id, worktree)?,
-            args: vec![],
-            env: Default::default(),
+            command: resolve(worktree, &path, "lsp.dhall.binary.path")?,
+            args: binary.arguments.unwrap_or_default(),
+            env: binary.env.into_iter().flatten().collect(),
         })
     }
 
@@ -48,102 +189,247 @@ impl DhallLanguageServer {
         language_server_id: &LanguageServerId,
         worktree: &zed::Worktree,
     ) -> Result<String> {
-        let (platform, arch) = zed::current_platform();
-        let binary_name = if let zed_extension_api::Os::Windows = platform {
-            "dhall-lsp-server.exe"
-        } else {
-            "dhall-lsp-server"
-        };
+        let binary_name = cli::executable_name("dhall-lsp-server");
+
+        // The convention other editors' Dhall integrations follow, and a
+        // simple way to point containerized setups at their server.
+        let from_env = worktree
+            .shell_env()
+            .into_iter()
+            .find(|(key, value)| key == SERVER_ENV_VAR && !value.is_empty());
+        if let Some((_, path)) = from_env {
+            return resolve(worktree, &path, SERVER_ENV_VAR);
+        }
+
+        if let Some(path) = cli::find_on_path(worktree, &binary_name) {
+            return Ok(path);
+        }
 
-        if let Some(path) = worktree.which(binary_name) {
+        // Worktrees pinned to a release each use their own, rather than the
+        // one shared by the others.
+        if let Some(version) = toolchain::pinned_version(worktree)? {
+            let release_dir = format!("dhall-haskell-{version}");
+            let path =
+                match toolchain::installed_in(&release_dir, "dhall-lsp-server", "dhall-lsp-server")
+                {
+                    Some(path) => path,
+                    None => {
+                        let retained = DhallSettings::for_worktree(worktree)?.retained_releases;
+                        Self::install_release(
+                            Some(language_server_id),
+                            Some(&version),
+                            &worktree.root_path(),
+                            retained,
+                            self.cached_binary_path.as_deref(),
+                        )?
+                    }
+                };
+            toolchain::mark_pinned(&release_dir, &worktree.root_path());
             return Ok(path);
         }
+        toolchain::unpin(&worktree.root_path());
 
+        // A newer release found while the last server ran is installed
+        // before this one starts. If that fails, the installed release is
+        // started instead, and the next check finds the newer one again.
+        if self.update_available.take().is_some() {
+            let retained = DhallSettings::for_worktree(worktree)?.retained_releases;
+            let result = Self::install_release(
+                Some(language_server_id),
+                None,
+                &worktree.root_path(),
+                retained,
+                self.cached_binary_path.as_deref(),
+            );
+            zed::set_language_server_installation_status(
+                language_server_id,
+                &zed::LanguageServerInstallationStatus::None,
+            );
+            if let Ok(path) = result {
+                self.cached_binary_path = Some(path);
+            }
+            self.save_state();
+        }
+
+        // Whatever release is installed is started straight away, so that a
+        // slow or failing GitHub lookup doesn't hold up diagnostics. Newer
+        // releases are looked for once the server is running, by
+        // `check_for_update`, or installed by `/dhall-version update`.
+        let due = now().saturating_sub(self.checked_at) >= UPDATE_INTERVAL.as_secs();
         if let Some(path) = &self.cached_binary_path {
             if fs::metadata(path).is_ok_and(|stat| stat.is_file()) {
+                self.update_due = due;
                 return Ok(path.clone());
             }
         }
 
-        zed::set_language_server_installation_status(
-            language_server_id,
-            &zed::LanguageServerInstallationStatus::CheckingForUpdate,
-        );
-        let release = zed::latest_github_release(
-            "dhall-lang/dhall-haskell",
-            zed::GithubReleaseOptions {
-                require_assets: true,
-                pre_release: false,
-            },
-        )?;
-
-        let (file_suffix, download_type) = match (platform, arch) {
-            (zed::Os::Mac, zed::Architecture::Aarch64) => (
-                "aarch64-darwin.tar.bz2",
-                zed::DownloadedFileType::Uncompressed,
-            ),
-            (zed::Os::Mac, zed::Architecture::X8664) => (
-                "x86_64-darwin.tar.bz2",
-                zed::DownloadedFileType::Uncompressed,
-            ),
-            (zed::Os::Linux, zed::Architecture::X8664) => (
-                "x86_64-linux.tar.bz2",
-                zed::DownloadedFileType::Uncompressed,
-            ),
-            (zed::Os::Windows, zed::Architecture::X8664) => {
-                ("x86_64-windows.zip", zed::DownloadedFileType::Zip)
+        let binary_path = match toolchain::installed("dhall-lsp-server", "dhall-lsp-server") {
+            Some(path) => {
+                self.update_due = due;
+                path
             }
-            (platform, arch) => {
-                return Err(format!(
-                    "unsupported platform/arch combination: {platform:?}/{arch:?}"
-                ))
+            None => {
+                let retained = DhallSettings::for_worktree(worktree)?.retained_releases;
+                let path = Self::install_release(
+                    Some(language_server_id),
+                    None,
+                    &worktree.root_path(),
+                    retained,
+                    None,
+                )?;
+                self.checked_at = now();
+                path
             }
         };
-        let asset = release
-            .assets
-            .iter()
-            .find(|asset| {
-                asset.name.starts_with("dhall-lsp-server") && asset.name.ends_with(file_suffix)
-            })
-            .ok_or_else(|| format!("no asset found matching dhall-lsp-server-*-{file_suffix}"))?;
-        let version_dir = format!("dhall-haskell-{}", release.version);
+        self.cached_binary_path = Some(binary_path.clone());
+        self.save_state();
+        Ok(binary_path)
+    }
 
-        let binary_path = format!("{version_dir}/bin/{binary_name}");
-        let download_path = format!("{version_dir}/{}", asset.name);
-        if !fs::metadata(&binary_path).is_ok_and(|stat| stat.is_file()) {
-            zed::set_language_server_installation_status(
-                language_server_id,
-                &zed::LanguageServerInstallationStatus::Downloading,
-            );
+    /// Looks for a newer release of the language server, at most once a
+    /// day, if the running server was started from an installed one. Zed has
+    /// no hook for after a server starts, so this runs when it first asks for
+    /// the workspace configuration. Only the latest version is looked up
+    /// here; the release itself is installed the next time the server starts.
+    pub fn check_for_update(&mut self) -> Result<()> {
+        if !self.update_due {
+            return Ok(());
+        }
+        self.update_due = false;
+        self.checked_at = now();
+        let result = toolchain::latest_release().map(|release| {
+            let running = self
+                .cached_binary_path
+                .as_deref()
+                .and_then(toolchain::release_of);
+            let latest = format!("dhall-haskell-{}", release.version);
+            self.update_available =
+                (running.as_deref() != Some(latest.as_str())).then_some(release.version);
+        });
+        self.save_state();
+        result
+    }
 
-            zed::download_file(&asset.download_url, &version_dir, download_type)
-                .map_err(|e| format!("failed to download file: {e}"))?;
-
-            if download_type == zed::DownloadedFileType::Uncompressed {
-                // These are .tar.bz2, we need to manually uncompress them
-                let exit_status = std::process::Command::new("tar")
-                    .arg("-xf")
-                    .arg(&download_path)
-                    .status()
-                    .map_err(|e| format!("failed to decompress {download_path}: {e:?}"))?;
-                if !exit_status.success() {
-                    return Err(format!(
-                        "failed to decompress {download_path}: status {exit_status:?}"
-                    ));
-                }
+    /// Installs the language server from the given release, or the newest,
+    /// unless it is already installed, returning the path of its binary and
+    /// keeping the `retained` newest releases. A given release is recorded as
+    /// pinned by the worktree at `root`, and the release of the `running`
+    /// server's binary is kept too. Progress is shown for
+    /// `language_server_id`, if given.
+    pub fn install_release(
+        language_server_id: Option<&LanguageServerId>,
+        version: Option<&str>,
+        root: &str,
+        retained: usize,
+        running: Option<&str>,
+    ) -> Result<String> {
+        let set_status = |status: &zed::LanguageServerInstallationStatus| {
+            if let Some(id) = language_server_id {
+                zed::set_language_server_installation_status(id, status);
             }
+        };
+        let binary_name = cli::executable_name("dhall-lsp-server");
 
-            let entries =
-                fs::read_dir(".").map_err(|e| format!("failed to list working directory {e}"))?;
-            for entry in entries {
-                let entry = entry.map_err(|e| format!("failed to load directory entry {e}"))?;
-                if entry.file_name().to_str() != Some(&version_dir) {
-                    fs::remove_dir_all(entry.path()).ok();
-                }
-            }
+        if let Err(e) = toolchain::check_writable() {
+            set_status(&zed::LanguageServerInstallationStatus::Failed(e.clone()));
+            return Err(e);
         }
+        set_status(&zed::LanguageServerInstallationStatus::CheckingForUpdate);
+        let release = toolchain::release(version)?;
+        let asset = toolchain::release_asset(&release, "dhall-lsp-server")?;
+        let version_dir = format!("dhall-haskell-{}", release.version);
 
-        self.cached_binary_path = Some(binary_path.clone());
-        Ok(binary_path)
+        if let Some(path) = toolchain::installed_binary(&version_dir, &binary_name) {
+            return Ok(path);
+        }
+        set_status(&zed::LanguageServerInstallationStatus::Downloading);
+        let path = toolchain::install(asset, &version_dir, &binary_name)?;
+
+        if version.is_some() {
+            toolchain::mark_pinned(&version_dir, root);
+        }
+        toolchain::remove_stale_releases(retained, running);
+        Ok(path)
+    }
+}
+
+/// Returns `path`, configured by `origin`, as a command to run: either a
+/// path to a binary or a bare name to look up on the `PATH`.
+fn resolve(worktree: &zed::Worktree, path: &str, origin: &str) -> Result<String> {
+    if path.contains(['/', '\\']) {
+        return Ok(path.to_string());
+    }
+    cli::find_on_path(worktree, &cli::executable_name(path))
+        .ok_or_else(|| format!("could not find `{path}` ({origin}) on the PATH"))
+}
+
+/// Wraps `command` so that the server runs at a lower CPU priority, keeping
+/// the editor responsive while it checks a large file: with `nice` on macOS
+/// and Linux, and `start /belownormal` on Windows.
+pub fn low_priority(command: zed::Command, worktree: &zed::Worktree) -> Result<zed::Command> {
+    let (prefix, wrapper) = match zed::current_platform().0 {
+        zed::Os::Windows => (
+            vec!["/c", "start", "", "/belownormal", "/b", "/wait"],
+            cli::which(worktree, "cmd")?,
+        ),
+        _ => (vec!["-n", "10"], cli::which(worktree, "nice")?),
+    };
+    let mut args: Vec<String> = prefix.into_iter().map(String::from).collect();
+    args.push(command.command);
+    args.extend(command.args);
+    Ok(zed::Command {
+        command: wrapper,
+        args,
+        env: command.env,
+    })
+}
+
+/// What the filter in front of the language server changes in its output,
+/// passed to `language-server/filter.js` as `DHALL_LSP_FILTER`.
+#[derive(Debug, Serialize)]
+pub struct Filter {
+    /// Drop diagnostics and advertise only formatting, for projects that
+    /// type-check in CI instead.
+    pub formatter_only: bool,
+    /// Truncate diagnostic messages to this many characters.
+    pub max_diagnostic_length: Option<usize>,
+    /// Keep the long explanation attached to type errors.
+    pub explain: bool,
+}
+
+impl Filter {
+    pub fn new(settings: &DhallSettings) -> Self {
+        Self {
+            formatter_only: settings.formatter_only,
+            max_diagnostic_length: settings.max_diagnostic_length,
+            explain: settings.explain_diagnostics,
+        }
+    }
+
+    /// Whether the server's output needs filtering at all.
+    pub fn is_active(&self) -> bool {
+        self.formatter_only || self.max_diagnostic_length.is_some() || !self.explain
     }
 }
+
+/// Wraps the language server `command` in `language-server/filter.js`,
+/// which rewrites the server's messages as `filter` asks.
+pub fn filtered(command: zed::Command, filter: &Filter) -> Result<zed::Command> {
+    fs::create_dir_all(SCRIPT_DIR).map_err(|e| format!("failed to create {SCRIPT_DIR}: {e}"))?;
+    let script = env::current_dir()
+        .map_err(|e| format!("failed to locate working directory: {e}"))?
+        .join(SCRIPT_DIR)
+        .join("filter.js");
+    fs::write(&script, FILTER_SCRIPT)
+        .map_err(|e| format!("failed to write {}: {e}", script.display()))?;
+    let options = serde_json::to_string(filter).map_err(|e| e.to_string())?;
+    let mut args = vec![script.to_string_lossy().into_owned(), command.command];
+    args.extend(command.args);
+    let mut env = command.env;
+    env.push(("DHALL_LSP_FILTER".to_string(), options));
+    Ok(zed::Command {
+        command: zed::node_binary_path()?,
+        args,
+        env,
+    })
+}
@@ -0,0 +1,51 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::ops::{Range, RangeInclusive};
+
+use zed_extension_api::Result;
+
+/// Parses a `<file>:<line>` slash command argument.
+pub fn parse(location: &str) -> Result<(&str, usize)> {
+    location
+        .rsplit_once(':')
+        .and_then(|(path, line)| Some((path, line.parse::<usize>().ok()?)))
+        .ok_or_else(|| format!("expected <file>:<line>, got {location}"))
+}
+
+/// Parses a `<file>:<line>` or `<file>:<first>-<last>` slash command argument,
+/// returning an inclusive range of lines.
+pub fn parse_lines(location: &str) -> Result<(&str, RangeInclusive<usize>)> {
+    location
+        .rsplit_once(':')
+        .and_then(|(path, lines)| {
+            let (first, last) = lines.split_once('-').unwrap_or((lines, lines));
+            let (first, last) = (first.parse().ok()?, last.parse().ok()?);
+            (first <= last).then_some((path, first..=last))
+        })
+        .ok_or_else(|| format!("expected <file>:<first>-<last>, got {location}"))
+}
+
+/// Returns the byte range of the given (1-based) line, without its newline.
+pub fn line_span(source: &str, line: usize) -> Range<usize> {
+    let start = source
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum::<usize>();
+    let end = start + source[start..].find('\n').unwrap_or(source.len() - start);
+    start..end
+}
@@ -0,0 +1,117 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use zed_extension_api::{self as zed, Result, SlashCommandOutput, SlashCommandOutputSection};
+
+use crate::settings::DhallSettings;
+use crate::{audit, imports, worktree};
+
+pub const SLASH_COMMAND: &str = "dhall-mirror";
+
+/// URL prefixes and the mirrors that replace them.
+pub type Mirrors = BTreeMap<String, String>;
+
+/// The mirrors from the most recently read settings.
+static CURRENT: Mutex<Mirrors> = Mutex::new(BTreeMap::new());
+
+pub fn set_current(mirrors: Mirrors) {
+    if let Ok(mut current) = CURRENT.lock() {
+        *current = mirrors;
+    }
+}
+
+fn remap_with(mirrors: &Mirrors, url: &str) -> Option<String> {
+    mirrors
+        .iter()
+        .filter(|(prefix, _)| url.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(prefix, mirror)| format!("{mirror}{}", &url[prefix.len()..]))
+}
+
+/// Returns the URL to fetch in place of `url`, which is `url` itself unless
+/// a mirror is configured for it.
+pub fn remap(url: &str) -> String {
+    CURRENT
+        .lock()
+        .ok()
+        .and_then(|mirrors| remap_with(&mirrors, url))
+        .unwrap_or_else(|| url.to_string())
+}
+
+/// Lists imports that have a configured mirror, and with `apply`, rewrites
+/// them to use it. Integrity checks are kept, since mirrors serve the same
+/// contents.
+pub fn run_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let apply = match args.first().map(String::as_str) {
+        Some("apply") => true,
+        Some(_) => return Err("usage: /dhall-mirror [apply]".to_string()),
+        None => false,
+    };
+    let mirrors = DhallSettings::for_worktree(worktree)?.mirrors;
+    if mirrors.is_empty() {
+        return Err("no mirrors are configured in lsp.dhall.settings.mirrors".to_string());
+    }
+    let root = PathBuf::from(worktree.root_path());
+    let found = audit::find_imports(&root, |import| {
+        remap_with(&mirrors, &import.target).is_some()
+    });
+
+    let mut text = String::new();
+    if found.is_empty() {
+        text.push_str("No imports refer to a mirrored URL.\n");
+    } else if apply {
+        let mut files: Vec<&str> = found.iter().map(|found| found.file.as_str()).collect();
+        files.dedup();
+        let mut rewrites = Vec::new();
+        for file in files {
+            let source = fs::read_to_string(root.join(file))
+                .map_err(|e| format!("failed to read {file}: {e}"))?;
+            if let Some(rewritten) =
+                imports::rewrite_imports(&source, |import| remap_with(&mirrors, &import.target))
+            {
+                rewrites.push((file.to_string(), rewritten));
+            }
+        }
+        worktree::write_files(&root, &rewrites)?;
+        for (file, _) in &rewrites {
+            text.push_str(&format!("- rewrote {file}\n"));
+        }
+    } else {
+        for found in &found {
+            let mirror = remap_with(&mirrors, &found.import.target).unwrap_or_default();
+            text.push_str(&format!(
+                "- {}:{}: {} → {mirror}\n",
+                found.file, found.line, found.import.target
+            ));
+        }
+        text.push_str("\nRun `/dhall-mirror apply` to rewrite these imports.\n");
+    }
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: "Mirrored imports".to_string(),
+        }],
+        text,
+    })
+}
@@ -0,0 +1,105 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use zed_extension_api::{self as zed, Result, SlashCommandOutput, SlashCommandOutputSection};
+
+use crate::{audit, cache};
+
+pub const SLASH_COMMAND: &str = "dhall-offline";
+
+/// Whether the most recently read settings enabled offline mode.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// An address nothing listens on, used as the proxy for every request so
+/// that `dhall` fails fast instead of reaching the network.
+const UNREACHABLE_PROXY: &str = "http://127.0.0.1:9";
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Returns the environment that keeps Dhall's resolver off the network in
+/// offline mode, leaving it to the cache and local (e.g. vendored) files.
+pub fn env() -> Vec<(String, String)> {
+    if !is_enabled() {
+        return Vec::new();
+    }
+    ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"]
+        .into_iter()
+        .map(|name| (name.to_string(), UNREACHABLE_PROXY.to_string()))
+        .chain([("NO_PROXY".to_string(), String::new())])
+        .chain([("no_proxy".to_string(), String::new())])
+        .collect()
+}
+
+/// Lists the remote imports that can't be resolved without the network:
+/// those without an integrity check, and those whose hash isn't cached.
+pub fn run_slash_command(
+    _args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let root = PathBuf::from(worktree.root_path());
+    let cache_dir = cache::cache_dir(worktree)?;
+    let remote = audit::find_imports(&root, |import| import.target.contains("://"));
+
+    let mut text = String::new();
+    let mut online = 0;
+    for found in &remote {
+        let reason = match &found.hash {
+            None => "not pinned",
+            Some(hash) => {
+                let hash = hash.trim_start_matches("sha256:");
+                if cache::entry_path(&cache_dir, hash).exists() {
+                    continue;
+                }
+                "not cached"
+            }
+        };
+        online += 1;
+        text.push_str(&format!(
+            "- {}:{}: {} ({reason})\n",
+            found.file, found.line, found.import.target
+        ));
+    }
+    if online == 0 {
+        text.push_str("Every remote import resolves from the cache.\n");
+    } else {
+        text.push_str(&format!(
+            "\n{online} of {} remote imports need the network. Pin them with `dhall freeze`, \
+             then cache them with `/dhall-cache-warm` or vendor them with `/dhall-vendor`.\n",
+            remote.len()
+        ));
+    }
+    if is_enabled() {
+        text.push_str(
+            "\nOffline mode is on: the extension and the language server won't fetch them.\n",
+        );
+    }
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: "Offline resolution".to_string(),
+        }],
+        text,
+    })
+}
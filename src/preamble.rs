@@ -0,0 +1,113 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use zed_extension_api::{self as zed, Result, SlashCommandOutput, SlashCommandOutputSection};
+
+use crate::{cli, prelude};
+
+pub const SLASH_COMMAND: &str = "dhall-context";
+
+/// The major version of the Dhall standard each dhall-haskell release
+/// implements, by release series.
+const STANDARD_VERSIONS: &[(&str, &str)] = &[
+    ("1.42", "23"),
+    ("1.41", "22"),
+    ("1.40", "21"),
+    ("1.39", "20"),
+    ("1.38", "20"),
+];
+
+/// Runs `program --version`, returning the version it prints. Managed
+/// installs are relative to the extension's working directory.
+pub fn version(program: &str, cwd: &Path) -> Option<String> {
+    let program = env::current_dir().ok()?.join(program);
+    let output = cli::run(&program.to_string_lossy(), &["--version"], cwd, None).ok()?;
+    output
+        .success
+        .then(|| output.stdout.trim().to_string())
+        .filter(|version| !version.is_empty())
+}
+
+fn standard_version(dhall_version: &str) -> Option<&'static str> {
+    STANDARD_VERSIONS
+        .iter()
+        .find(|(series, _)| {
+            dhall_version
+                .strip_prefix(series)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+        })
+        .map(|(_, standard)| *standard)
+}
+
+/// Describes the Dhall versions the worktree uses: the language server at
+/// `language_server`, `dhall` and the standard it implements, and the
+/// Prelude releases its files import. The assistant is asked to stick to
+/// them.
+pub fn preamble(worktree: &zed::Worktree, language_server: Option<&str>) -> String {
+    let root = PathBuf::from(worktree.root_path());
+    let mut lines = Vec::new();
+    if let Some(version) = language_server.and_then(|path| version(path, &root)) {
+        lines.push(format!("- dhall-lsp-server {version}"));
+    }
+    if let Some(version) = cli::which(worktree, "dhall")
+        .ok()
+        .and_then(|dhall| version(&dhall, &root))
+    {
+        match standard_version(&version) {
+            Some(standard) => lines.push(format!(
+                "- dhall {version}, implementing version {standard} of the Dhall standard"
+            )),
+            None => lines.push(format!("- dhall {version}")),
+        }
+    }
+    match prelude::project_versions(&root).as_slice() {
+        [] => {}
+        [version] => lines.push(format!("- the Prelude {version}")),
+        versions => lines.push(format!("- the Prelude {}", versions.join(" and "))),
+    }
+    if lines.is_empty() {
+        return String::new();
+    }
+    format!(
+        "This Dhall project uses:\n\n{}\n\nOnly suggest syntax, builtins and Prelude functions \
+         available in these versions.\n",
+        lines.join("\n")
+    )
+}
+
+/// Adds the preamble to the conversation, using the language server at
+/// `language_server` if it has been installed, or else the one on the `PATH`.
+pub fn run_slash_command(
+    _args: Vec<String>,
+    worktree: &zed::Worktree,
+    language_server: Option<&str>,
+) -> Result<SlashCommandOutput> {
+    let on_path = cli::find_on_path(worktree, &cli::executable_name("dhall-lsp-server"));
+    let text = preamble(worktree, language_server.or(on_path.as_deref()));
+    if text.is_empty() {
+        return Err("could not find dhall, dhall-lsp-server or Prelude imports".to_string());
+    }
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: "Dhall versions".to_string(),
+        }],
+        text,
+    })
+}
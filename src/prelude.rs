@@ -0,0 +1,239 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use zed_extension_api::{self as zed, Result, SlashCommandOutput, SlashCommandOutputSection};
+
+use crate::settings::DhallSettings;
+use crate::{cli, imports, worktree};
+
+pub const SLASH_COMMAND: &str = "dhall-prelude";
+pub const BUMP_SLASH_COMMAND: &str = "dhall-prelude-bump";
+
+pub fn latest_version() -> Result<String> {
+    let release = zed::latest_github_release(
+        "dhall-lang/dhall-lang",
+        zed::GithubReleaseOptions {
+            require_assets: false,
+            pre_release: false,
+        },
+    )?;
+    Ok(normalize_version(&release.version))
+}
+
+pub fn normalize_version(version: &str) -> String {
+    format!("v{}", version.trim().trim_start_matches('v'))
+}
+
+pub fn package_url(version: &str) -> String {
+    format!("https://prelude.dhall-lang.org/{version}/package.dhall")
+}
+
+/// Computes the semantic hash of an import with `dhall hash`.
+pub fn semantic_hash(dhall: &str, cwd: &Path, import: &str) -> Result<String> {
+    let output = cli::run(dhall, &["hash"], cwd, Some(import))?;
+    if !output.success {
+        return Err(format!("failed to hash {import}: {}", output.stderr.trim()));
+    }
+    Ok(output.stdout.trim().to_string())
+}
+
+pub fn run_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let version = match args.first() {
+        Some(version) => normalize_version(version),
+        None => match DhallSettings::for_worktree(worktree)?.prelude_version {
+            Some(version) => normalize_version(&version),
+            None => latest_version()?,
+        },
+    };
+
+    let dhall = cli::which(worktree, "dhall")?;
+    let url = package_url(&version);
+    let hash = semantic_hash(&dhall, &PathBuf::from(worktree.root_path()), &url)?;
+
+    let text = format!("let Prelude =\n      {url}\n        {hash}\n\n");
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: format!("Prelude {version}"),
+        }],
+        text,
+    })
+}
+
+/// Locates the version segment of a Prelude URL, e.g. `v23.0.0` in
+/// `https://prelude.dhall-lang.org/v23.0.0/List/map.dhall`.
+pub fn version_in_url(url: &str) -> Option<Range<usize>> {
+    let prefixes = [
+        "https://prelude.dhall-lang.org/",
+        "http://prelude.dhall-lang.org/",
+        "https://raw.githubusercontent.com/dhall-lang/dhall-lang/",
+    ];
+    let start = prefixes
+        .iter()
+        .find_map(|prefix| url.strip_prefix(prefix).map(|_| prefix.len()))?;
+    let end = start + url[start..].find('/')?;
+    let version = &url[start..end];
+    let is_version =
+        version.starts_with('v') && version[1..].split('.').all(|n| n.parse::<u32>().is_ok());
+    is_version.then_some(start..end)
+}
+
+/// Returns the distinct Prelude versions imported by files in the worktree
+/// at `root`, newest first.
+pub fn project_versions(root: &Path) -> Vec<String> {
+    let mut versions: Vec<String> = Vec::new();
+    for file in worktree::dhall_files(root) {
+        let Ok(source) = fs::read_to_string(root.join(&file)) else {
+            continue;
+        };
+        for import in imports::imports(&source) {
+            let Some(segment) = version_in_url(&import.target) else {
+                continue;
+            };
+            let version = &import.target[segment];
+            if !versions.iter().any(|v| v == version) {
+                versions.push(version.to_string());
+            }
+        }
+    }
+    versions.sort_by_key(|version| version_key(version));
+    versions.reverse();
+    versions
+}
+
+/// The Prelude release that docs for the worktree should describe: the
+/// newest version its files import, then `prelude_version`, then the latest.
+pub fn docs_version(worktree: &zed::Worktree) -> Result<String> {
+    if let Some(version) = project_versions(Path::new(&worktree.root_path()))
+        .into_iter()
+        .next()
+    {
+        return Ok(version);
+    }
+    match DhallSettings::for_worktree(worktree)?.prelude_version {
+        Some(version) => Ok(normalize_version(&version)),
+        None => latest_version(),
+    }
+}
+
+/// Orders versions like `v23.0.0` numerically.
+pub fn version_key(version: &str) -> Vec<u32> {
+    version
+        .trim_start_matches('v')
+        .split('.')
+        .map(|n| n.parse::<u32>().unwrap_or_default())
+        .collect()
+}
+
+pub struct Bump {
+    pub file: String,
+    pub imports: usize,
+}
+
+/// Rewrites every Prelude import in the worktree to `version`, re-computing
+/// the integrity check of imports that were frozen.
+pub fn bump(dhall: &str, root: &Path, version: &str) -> Result<Vec<Bump>> {
+    let mut hashes: HashMap<String, String> = HashMap::new();
+    let mut bumps = Vec::new();
+    let mut rewritten = Vec::new();
+    for file in worktree::dhall_files(root) {
+        let path = root.join(&file);
+        let Ok(source) = fs::read_to_string(&path) else {
+            continue;
+        };
+
+        let mut output = String::with_capacity(source.len());
+        let mut last = 0;
+        let mut count = 0;
+        for import in imports::imports(&source) {
+            let Some(segment) = version_in_url(&import.target) else {
+                continue;
+            };
+            if &import.target[segment.clone()] == version {
+                continue;
+            }
+            let url = format!(
+                "{}{version}{}",
+                &import.target[..segment.start],
+                &import.target[segment.end..]
+            );
+            output.push_str(&source[last..import.span.start]);
+            output.push_str(&url);
+            last = import.span.start + import.target.len();
+
+            if let Some(hash) = import.hash {
+                let new_hash = match hashes.get(&url) {
+                    Some(new_hash) => new_hash.clone(),
+                    None => {
+                        let new_hash = semantic_hash(dhall, root, &url)?;
+                        hashes.insert(url.clone(), new_hash.clone());
+                        new_hash
+                    }
+                };
+                output.push_str(&source[last..hash.start]);
+                output.push_str(&new_hash);
+                last = hash.end;
+            }
+            count += 1;
+        }
+
+        if count > 0 {
+            output.push_str(&source[last..]);
+            rewritten.push((file.clone(), output));
+            bumps.push(Bump {
+                file,
+                imports: count,
+            });
+        }
+    }
+    worktree::write_files(root, &rewritten)?;
+    Ok(bumps)
+}
+
+pub fn run_bump_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let version = match args.first() {
+        Some(version) => normalize_version(version),
+        None => latest_version()?,
+    };
+    let dhall = cli::which(worktree, "dhall")?;
+    let bumps = bump(&dhall, &PathBuf::from(worktree.root_path()), &version)?;
+
+    let mut text = format!(
+        "Updated Prelude imports to {version} in {} files\n",
+        bumps.len()
+    );
+    for bump in &bumps {
+        text.push_str(&format!("- {} ({} imports)\n", bump.file, bump.imports));
+    }
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: format!("Prelude {version}"),
+        }],
+        text,
+    })
+}
@@ -0,0 +1,197 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::ops::Range;
+
+use zed_extension_api::{self as zed, Result, SlashCommandOutput, SlashCommandOutputSection};
+
+use crate::location;
+use crate::syntax::{self, TokenKind};
+
+pub const SORT_FIELDS_SLASH_COMMAND: &str = "dhall-sort-fields";
+pub const LET_TO_RECORD_SLASH_COMMAND: &str = "dhall-let-to-record";
+
+pub struct Record {
+    pub span: Range<usize>,
+    pub fields: Vec<Field>,
+}
+
+pub struct Field {
+    pub name: String,
+    pub span: Range<usize>,
+}
+
+/// Returns every record literal and record type in `source`.
+pub fn records(source: &str) -> Vec<Record> {
+    let tokens = syntax::tokenize(source);
+    let mut records = Vec::new();
+    let mut open = Vec::new();
+    for (ix, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::Open => open.push(ix),
+            TokenKind::Close => {
+                let Some(start) = open.pop() else {
+                    continue;
+                };
+                if tokens[start].is(source, "{") && token.is(source, "}") {
+                    records.push(Record {
+                        span: tokens[start].span.start..token.span.end,
+                        fields: fields(source, &tokens[start + 1..ix]),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    records
+}
+
+fn fields(source: &str, tokens: &[syntax::Token]) -> Vec<Field> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    while start < tokens.len() {
+        let end = syntax::find_top_level(tokens, start, |token| token.is(source, ","));
+        let field = &tokens[start..end];
+        if let Some(name) = field.iter().find(|t| t.kind != TokenKind::Comment) {
+            if name.kind == TokenKind::Label {
+                fields.push(Field {
+                    name: name.text(source).trim_matches('`').to_string(),
+                    span: syntax::span_between(tokens, start, end),
+                });
+            }
+        }
+        start = end + 1;
+    }
+    fields
+}
+
+/// Renders `record` with its fields in alphabetical order, preserving the
+/// single-line or `dhall format`-style multi-line layout of the original.
+pub fn sort_fields(source: &str, record: &Record) -> String {
+    let mut fields: Vec<&Field> = record.fields.iter().collect();
+    fields.sort_by_key(|field| field.name.to_lowercase());
+    let fields: Vec<&str> = fields
+        .into_iter()
+        .map(|field| source[field.span.clone()].trim())
+        .collect();
+
+    let original = &source[record.span.clone()];
+    if original.contains('\n') {
+        let line_start = source[..record.span.start]
+            .rfind('\n')
+            .map_or(0, |ix| ix + 1);
+        let indent = " ".repeat(source[line_start..record.span.start].chars().count());
+        let separator = format!("\n{indent}, ");
+        format!("{{ {}\n{indent}}}", fields.join(&separator))
+    } else {
+        format!("{{ {} }}", fields.join(", "))
+    }
+}
+
+/// Finds the innermost record spanning the given (1-based) line.
+pub fn record_at_line(source: &str, line: usize) -> Option<Record> {
+    let Range { start, end } = location::line_span(source, line);
+    records(source)
+        .into_iter()
+        .filter(|record| record.span.start <= end && start <= record.span.end)
+        .filter(|record| !record.fields.is_empty())
+        .min_by_key(|record| record.span.len())
+}
+
+pub fn run_sort_fields_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let (path, line) = location::parse(
+        args.first()
+            .ok_or_else(|| "usage: /dhall-sort-fields <file>:<line>".to_string())?,
+    )?;
+
+    let source = worktree.read_text_file(path)?;
+    let record =
+        record_at_line(&source, line).ok_or_else(|| format!("no record found at {path}:{line}"))?;
+    let text = format!("{}\n", sort_fields(&source, &record));
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: format!("Sorted record at {path}:{line}"),
+        }],
+        text,
+    })
+}
+
+/// Rewrites the body of a file's top-level `let` chain into a record that
+/// exports every binding, e.g. `let a = 1 let b = 2 in a + b` becomes
+/// `let a = 1 let b = 2 in { a, b, result = a + b }`.
+pub fn let_chain_to_record(source: &str) -> Option<String> {
+    let chain = syntax::let_chain(source);
+    if chain.bindings.is_empty() {
+        return None;
+    }
+
+    let mut names: Vec<String> = chain
+        .bindings
+        .iter()
+        .map(|binding| quote_label(&binding.name))
+        .collect();
+    names.dedup();
+    let body = source[chain.body.clone()].trim();
+    let mut fields = names.clone();
+    if !body.is_empty() && !names.iter().any(|name| name == body) {
+        fields.push(format!("result = {body}"));
+    }
+
+    let last = chain.bindings.last()?;
+    let mut output = source[..last.span.end].to_string();
+    output.push_str("\n\nin  { ");
+    output.push_str(&fields.join("\n    , "));
+    output.push_str(if fields.len() > 1 {
+        "\n    }\n"
+    } else {
+        " }\n"
+    });
+    Some(output)
+}
+
+fn quote_label(name: &str) -> String {
+    let simple = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '/');
+    if simple {
+        name.to_string()
+    } else {
+        format!("`{name}`")
+    }
+}
+
+pub fn run_let_to_record_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let path = args
+        .first()
+        .ok_or_else(|| "usage: /dhall-let-to-record <file>".to_string())?;
+    let source = worktree.read_text_file(path)?;
+    let text = let_chain_to_record(&source)
+        .ok_or_else(|| format!("{path} has no top-level let bindings"))?;
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: format!("{path} as a record"),
+        }],
+        text,
+    })
+}
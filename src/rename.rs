@@ -0,0 +1,79 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use zed_extension_api::{self as zed, Result, SlashCommandOutput, SlashCommandOutputSection};
+
+use crate::imports;
+use crate::worktree;
+
+pub const SLASH_COMMAND: &str = "dhall-rename-imports";
+
+/// Updates imports after `old` has been moved to `new` (both relative to the
+/// worktree root), returning the files that were rewritten.
+pub fn rename(root: &Path, old: &str, new: &str) -> Result<Vec<String>> {
+    let mut rewrites = Vec::new();
+    for file in worktree::dhall_files(root) {
+        let Ok(source) = fs::read_to_string(root.join(&file)) else {
+            continue;
+        };
+        let rewritten = imports::rewrite_imports(&source, |import| {
+            // The moved file's own imports are relative to its old location.
+            let importer = if file == new { old } else { file.as_str() };
+            let target = imports::resolve_local(importer, &import.target)?;
+            if target != old && file != new {
+                return None;
+            }
+            let target = if target == old { new } else { &target };
+            let relative = imports::relative_import(&file, target);
+            (relative != import.target).then_some(relative)
+        });
+        if let Some(rewritten) = rewritten {
+            rewrites.push((file, rewritten));
+        }
+    }
+    worktree::write_files(root, &rewrites)?;
+    Ok(rewrites.into_iter().map(|(file, _)| file).collect())
+}
+
+pub fn run_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let [old, new] = args.as_slice() else {
+        return Err("usage: /dhall-rename-imports <old-path> <new-path>".to_string());
+    };
+    let (old, new) = (old.trim_start_matches("./"), new.trim_start_matches("./"));
+    let root = PathBuf::from(worktree.root_path());
+    let updated = rename(&root, old, new)?;
+
+    let mut text = format!(
+        "Updated imports of {old} → {new} in {} files\n",
+        updated.len()
+    );
+    for file in &updated {
+        text.push_str(&format!("- {file}\n"));
+    }
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: format!("Renamed {old}"),
+        }],
+        text,
+    })
+}
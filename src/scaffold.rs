@@ -0,0 +1,200 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::sync::Mutex;
+
+use zed_extension_api::{
+    self as zed, Result, SlashCommandArgumentCompletion, SlashCommandOutput,
+    SlashCommandOutputSection,
+};
+
+use crate::docs::{self, kubernetes};
+
+pub const SLASH_COMMAND: &str = "dhall-k8s";
+
+/// How many levels of required fields to fill in with nested resources.
+const MAX_DEPTH: usize = 3;
+
+/// The types of a dhall-kubernetes version, as their names and paths
+/// relative to the version.
+type Types = Vec<(String, String)>;
+
+/// The types of each version fetched so far.
+static TYPES: Mutex<Vec<(String, Types)>> = Mutex::new(Vec::new());
+
+fn types(version: &str) -> Result<Types> {
+    if let Some((_, types)) = TYPES
+        .lock()
+        .ok()
+        .and_then(|cached| cached.iter().find(|(v, _)| v == version).cloned())
+    {
+        return Ok(types);
+    }
+    let url = format!("{}/{version}/types.dhall", kubernetes::BASE_URL);
+    let types: Types = docs::package_entries(&docs::fetch_text(&url)?)
+        .into_iter()
+        .map(|(name, target)| (name, target.trim_start_matches("./").to_string()))
+        .collect();
+    if let Ok(mut cached) = TYPES.lock() {
+        cached.push((version.to_string(), types.clone()));
+    }
+    Ok(types)
+}
+
+struct Scaffold<'a> {
+    version: &'a str,
+    types: &'a [(String, String)],
+}
+
+impl Scaffold<'_> {
+    /// Returns the name of the type that a field's type, such as
+    /// `./io.k8s.api.apps.v1.DeploymentSpec.dhall`, refers to.
+    fn type_name(&self, import: &str) -> Option<&str> {
+        let file = import.trim_start_matches("./");
+        self.types
+            .iter()
+            .find(|(_, path)| path.trim_start_matches("types/") == file)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Returns `field_type` with its imports replaced by package types.
+    fn type_text(&self, field_type: &str) -> String {
+        field_type
+            .split(' ')
+            .map(|word| match self.type_name(word) {
+                Some(name) => format!("kubernetes.{name}.Type"),
+                None => word.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Returns a placeholder value for a field of type `field_type`.
+    fn placeholder(&self, field_type: &str, depth: usize) -> Result<String> {
+        let value = match field_type.split_once(' ') {
+            None => match field_type {
+                "Text" => "\"\"".to_string(),
+                "Natural" => "0".to_string(),
+                "Integer" => "+0".to_string(),
+                "Bool" => "False".to_string(),
+                import => match self.type_name(import) {
+                    Some(name) => self.resource(name, depth + 1)?,
+                    None => format!("{{- TODO: {import} -}}"),
+                },
+            },
+            Some(("List", element)) => format!("([] : List {})", self.type_text(element)),
+            Some(("Optional", element)) => format!("(None {})", self.type_text(element)),
+            Some(_) => format!("{{- TODO: {} -}}", self.type_text(field_type)),
+        };
+        Ok(value)
+    }
+
+    /// Returns `kubernetes.<name>::{ … }` with each field that has no default
+    /// filled in, along with the top-level `spec` and `metadata.name`.
+    fn resource(&self, name: &str, depth: usize) -> Result<String> {
+        let (_, path) = self
+            .types
+            .iter()
+            .find(|(type_name, _)| type_name == name)
+            .ok_or_else(|| format!("dhall-kubernetes {} has no {name}", self.version))?;
+        let base = format!("{}/{}", kubernetes::BASE_URL, self.version);
+        let qualified = path.trim_start_matches("types/");
+        let schema = docs::fetch_text(&format!("{base}/{path}"))?;
+        let defaults = docs::fetch_text(&format!("{base}/default/{qualified}"))
+            .map(|defaults| kubernetes::record_fields(&defaults, '='))
+            .unwrap_or_default();
+
+        let mut fields = Vec::new();
+        for (field, field_type) in kubernetes::record_fields(&schema, ':') {
+            let has_default = defaults.iter().any(|(name, _)| *name == field);
+            let value = if name == "ObjectMeta" && field == "name" {
+                "Some \"\"".to_string()
+            } else if !has_default && depth < MAX_DEPTH {
+                self.placeholder(&field_type, depth)?
+            } else if depth == 0 && field == "spec" {
+                match field_type.strip_prefix("Optional ") {
+                    Some(spec) => format!("Some {}", self.placeholder(spec, depth)?),
+                    None => continue,
+                }
+            } else {
+                continue;
+            };
+            fields.push(format!("{field} = {value}"));
+        }
+        Ok(if fields.is_empty() {
+            format!("kubernetes.{name}::{{=}}")
+        } else {
+            format!("kubernetes.{name}::{{ {} }}", fields.join(", "))
+        })
+    }
+}
+
+/// Completes the kind with the types of the given (or newest) version.
+pub fn complete_argument(args: Vec<String>) -> Result<Vec<SlashCommandArgumentCompletion>> {
+    if args.len() > 1 {
+        return Ok(kubernetes::VERSIONS
+            .iter()
+            .map(|version| SlashCommandArgumentCompletion {
+                label: version.to_string(),
+                new_text: version.to_string(),
+                run_command: true,
+            })
+            .collect());
+    }
+    let query = args
+        .first()
+        .map(|kind| kind.to_lowercase())
+        .unwrap_or_default();
+    Ok(types(kubernetes::VERSIONS[0])?
+        .into_iter()
+        .filter(|(name, _)| name.to_lowercase().contains(&query))
+        .map(|(name, path)| SlashCommandArgumentCompletion {
+            label: format!("{name} ({})", path.trim_start_matches("types/")),
+            new_text: name,
+            run_command: true,
+        })
+        .collect())
+}
+
+/// Writes a typed dhall-kubernetes resource of the given kind, with its
+/// defaults applied and placeholders for the fields that have none.
+pub fn run_slash_command(
+    args: Vec<String>,
+    _worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let kind = args
+        .first()
+        .ok_or_else(|| "usage: /dhall-k8s <kind> [kubernetes-version]".to_string())?;
+    let version = args.get(1).map_or(kubernetes::VERSIONS[0], String::as_str);
+    let types = types(version)?;
+    let resource = Scaffold {
+        version,
+        types: &types,
+    }
+    .resource(kind, 0)?;
+
+    let text = format!(
+        "let kubernetes =\n      {}/{version}/package.dhall\n\nin  {resource}\n",
+        kubernetes::BASE_URL
+    );
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: format!("{kind} (dhall-kubernetes {version})"),
+        }],
+        text,
+    })
+}
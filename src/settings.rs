@@ -0,0 +1,157 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use zed_extension_api::{
+    self as zed, serde_json,
+    settings::{ContextServerSettings, LspSettings},
+    Result,
+};
+
+use crate::context_server;
+use crate::headers::{self, Headers};
+use crate::language_server::DhallLanguageServer;
+use crate::mirrors::{self, Mirrors};
+use crate::offline;
+use crate::toolchain;
+
+/// Extension options, read from `lsp.dhall.settings` in Zed's settings.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct DhallSettings {
+    /// The Prelude release to pin, e.g. `v23.0.0`. Defaults to the latest.
+    pub prelude_version: Option<String>,
+    /// Package URLs to offer to the `dhall-package` docs provider.
+    pub docs_packages: Vec<String>,
+    pub concourse: ConcourseSettings,
+    /// HTTP headers to send with remote imports, per `host` or `host:port`.
+    pub headers: Headers,
+    /// A Dhall file, relative to the worktree, to use as `DHALL_HEADERS`.
+    pub headers_file: Option<String>,
+    /// URL prefixes to fetch from a mirror instead, e.g.
+    /// `https://prelude.dhall-lang.org/` to `https://prelude.mirror.corp/`.
+    pub mirrors: Mirrors,
+    /// Resolve imports only from the cache and local files.
+    pub offline: bool,
+    /// How many installed dhall-haskell releases to keep, newest first.
+    pub retained_releases: usize,
+    /// The dhall-haskell release to install for this worktree, e.g. `1.41.2`.
+    pub dhall_haskell_version: Option<String>,
+    /// Log how long each phase of an install takes to `install.log`.
+    pub log_install_timings: bool,
+    /// Use the language server only to format documents.
+    pub formatter_only: bool,
+    /// A file, relative to the worktree, for the language server's debug log.
+    pub trace_file: Option<String>,
+    /// Truncate diagnostic messages to this many characters.
+    pub max_diagnostic_length: Option<usize>,
+    /// Keep the long explanation `dhall` attaches to type errors.
+    pub explain_diagnostics: bool,
+    /// Run the language server at a lower CPU priority.
+    pub low_priority: bool,
+    /// The most memory, in megabytes, the language server's heap may use.
+    pub max_memory_mb: Option<u32>,
+}
+
+impl Default for DhallSettings {
+    fn default() -> Self {
+        Self {
+            prelude_version: None,
+            docs_packages: Vec::new(),
+            concourse: ConcourseSettings::default(),
+            headers: Headers::default(),
+            headers_file: None,
+            mirrors: Mirrors::default(),
+            offline: false,
+            retained_releases: 1,
+            dhall_haskell_version: None,
+            log_install_timings: false,
+            formatter_only: false,
+            trace_file: None,
+            max_diagnostic_length: None,
+            explain_diagnostics: true,
+            low_priority: false,
+            max_memory_mb: None,
+        }
+    }
+}
+
+/// Where `/dhall-concourse set` sends pipelines.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ConcourseSettings {
+    /// The `fly` target, e.g. `ci`.
+    pub target: Option<String>,
+    /// The pipeline name. Defaults to the name of the rendered file.
+    pub pipeline: Option<String>,
+}
+
+impl DhallSettings {
+    /// Puts the header, mirror and offline settings into effect for the
+    /// extension's requests and processes, returning the language server's
+    /// environment.
+    pub fn apply(&self, worktree: &zed::Worktree) -> Result<Vec<(String, String)>> {
+        mirrors::set_current(self.mirrors.clone());
+        offline::set_enabled(self.offline);
+        toolchain::set_log_timings(self.log_install_timings);
+        let mut env = headers::apply(
+            Path::new(&worktree.root_path()),
+            &self.headers,
+            self.headers_file.as_deref(),
+        )?;
+        env.extend(offline::env());
+        Ok(env)
+    }
+
+    pub fn for_worktree(worktree: &zed::Worktree) -> Result<Self> {
+        let settings =
+            LspSettings::for_worktree(DhallLanguageServer::LANGUAGE_SERVER_ID, worktree)?.settings;
+        match settings {
+            Some(settings) => serde_json::from_value(settings)
+                .map_err(|e| format!("invalid lsp.dhall.settings: {e}")),
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+/// What the assistant may do with the context server, read from
+/// `context_servers.dhall.settings`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ContextServerPermissions {
+    /// The tools to offer. Defaults to all of them.
+    pub tools: Option<Vec<String>>,
+    /// Allow fetching unpinned remote imports. Pinned imports are always
+    /// resolved from the cache.
+    pub remote_imports: bool,
+    /// Allow resolving `env:` imports.
+    pub env_imports: bool,
+}
+
+impl ContextServerPermissions {
+    pub fn for_project(project: &zed::Project) -> Result<Self> {
+        let settings =
+            ContextServerSettings::for_project(context_server::CONTEXT_SERVER_ID, project)?
+                .settings;
+        match settings {
+            Some(settings) => serde_json::from_value(settings)
+                .map_err(|e| format!("invalid context_servers.dhall.settings: {e}")),
+            None => Ok(Self::default()),
+        }
+    }
+}
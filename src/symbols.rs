@@ -0,0 +1,44 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use zed_extension_api::{self as zed, Result, SlashCommandOutput, SlashCommandOutputSection};
+
+use crate::syntax::{self, SymbolStyle};
+
+pub const SLASH_COMMAND: &str = "dhall-syntax";
+
+pub fn run_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let usage = || "usage: /dhall-syntax <ascii|unicode> <file>".to_string();
+    let style = match args.first().map(String::as_str) {
+        Some("ascii") => SymbolStyle::Ascii,
+        Some("unicode") => SymbolStyle::Unicode,
+        _ => return Err(usage()),
+    };
+    let path = args.get(1).ok_or_else(usage)?;
+
+    let source = worktree.read_text_file(path)?;
+    let text = syntax::convert_symbols(&source, style);
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: format!("{path} ({})", args[0]),
+        }],
+        text,
+    })
+}
@@ -0,0 +1,619 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! A lightweight lexical scanner for Dhall source.
+//!
+//! This is not a full parser: it understands just enough of the grammar
+//! (comments, text literals, imports, brackets and `let` chains) for the
+//! extension to locate bindings and imports without a round-trip through
+//! the `dhall` binary.
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Comment,
+    Text,
+    Label,
+    Number,
+    Url,
+    Path,
+    Env,
+    Hash,
+    Open,
+    Close,
+    Symbol,
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Range<usize>,
+}
+
+impl Token {
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.span.clone()]
+    }
+
+    pub fn is(&self, source: &str, text: &str) -> bool {
+        self.text(source) == text
+    }
+}
+
+pub fn tokenize(source: &str) -> Vec<Token> {
+    let mut lexer = Lexer {
+        source,
+        bytes: source.as_bytes(),
+        pos: 0,
+    };
+    let mut tokens = Vec::new();
+    while let Some(token) = lexer.next_token() {
+        tokens.push(token);
+    }
+    tokens
+}
+
+/// Returns the tokens of `source` with comments removed.
+pub fn code_tokens(source: &str) -> Vec<Token> {
+    tokenize(source)
+        .into_iter()
+        .filter(|token| token.kind != TokenKind::Comment)
+        .collect()
+}
+
+struct Lexer<'a> {
+    source: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn peek(&self, offset: usize) -> Option<u8> {
+        self.bytes.get(self.pos + offset).copied()
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.source[self.pos..]
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        while self.peek(0).is_some_and(|c| c.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        let c = self.peek(0)?;
+        let rest = self.rest();
+
+        let kind = if rest.starts_with("--") {
+            self.skip_while(|c| c != '\n');
+            TokenKind::Comment
+        } else if rest.starts_with("{-") {
+            self.skip_block_comment();
+            TokenKind::Comment
+        } else if c == b'"' {
+            self.pos += 1;
+            self.skip_double_quoted();
+            TokenKind::Text
+        } else if rest.starts_with("''") {
+            self.pos += 2;
+            self.skip_single_quoted();
+            TokenKind::Text
+        } else if c == b'`' {
+            self.pos += 1;
+            self.skip_while(|c| c != '`');
+            self.pos = (self.pos + 1).min(self.bytes.len());
+            TokenKind::Label
+        } else if rest.starts_with("https://") || rest.starts_with("http://") {
+            self.skip_while(|c| !is_import_terminator(c));
+            TokenKind::Url
+        } else if rest.starts_with("env:") {
+            self.pos += 4;
+            if self.peek(0) == Some(b'"') {
+                self.pos += 1;
+                self.skip_double_quoted();
+            } else {
+                self.skip_while(is_label_char);
+            }
+            TokenKind::Env
+        } else if rest.starts_with("sha256:") {
+            self.pos += 7;
+            self.skip_while(|c| c.is_ascii_hexdigit());
+            TokenKind::Hash
+        } else if is_path_start(rest) {
+            self.skip_while(|c| !is_import_terminator(c));
+            TokenKind::Path
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            self.skip_label();
+            TokenKind::Label
+        } else if c.is_ascii_digit()
+            || ((c == b'+' || c == b'-') && self.peek(1).is_some_and(|c| c.is_ascii_digit()))
+        {
+            self.pos += 1;
+            self.skip_while(|c| c.is_ascii_alphanumeric() || c == '.');
+            TokenKind::Number
+        } else if matches!(c, b'(' | b'[' | b'{' | b'<') {
+            self.pos += 1;
+            TokenKind::Open
+        } else if matches!(c, b')' | b']' | b'}' | b'>') {
+            self.pos += 1;
+            TokenKind::Close
+        } else {
+            let symbol = [
+                "//\\\\", "===", "->", "==", "!=", "&&", "||", "++", "::", "//", "/\\",
+            ]
+            .iter()
+            .find(|symbol| rest.starts_with(*symbol));
+            self.pos += match symbol {
+                Some(symbol) => symbol.len(),
+                None => rest.chars().next().map_or(1, char::len_utf8),
+            };
+            TokenKind::Symbol
+        };
+
+        Some(Token {
+            kind,
+            span: start..self.pos,
+        })
+    }
+
+    fn skip_while(&mut self, predicate: impl Fn(char) -> bool) {
+        for c in self.rest().chars() {
+            if !predicate(c) {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn skip_label(&mut self) {
+        loop {
+            self.skip_while(is_label_char);
+            // Builtins such as `Natural/fold` contain a slash.
+            if self.peek(0) == Some(b'/') && self.peek(1).is_some_and(|c| c.is_ascii_alphabetic()) {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn skip_block_comment(&mut self) {
+        let mut depth = 0;
+        while self.pos < self.bytes.len() {
+            let rest = self.rest();
+            if rest.starts_with("{-") {
+                depth += 1;
+                self.pos += 2;
+            } else if rest.starts_with("-}") {
+                depth -= 1;
+                self.pos += 2;
+                if depth == 0 {
+                    return;
+                }
+            } else {
+                self.pos += rest.chars().next().map_or(1, char::len_utf8);
+            }
+        }
+    }
+
+    fn skip_double_quoted(&mut self) {
+        while let Some(c) = self.peek(0) {
+            match c {
+                b'\\' => self.pos += 2,
+                b'"' => {
+                    self.pos += 1;
+                    return;
+                }
+                b'$' if self.peek(1) == Some(b'{') => {
+                    self.pos += 2;
+                    self.skip_interpolation();
+                }
+                _ => self.pos += 1,
+            }
+        }
+        self.pos = self.pos.min(self.bytes.len());
+    }
+
+    fn skip_single_quoted(&mut self) {
+        while self.pos < self.bytes.len() {
+            let rest = self.rest();
+            if rest.starts_with("''${") {
+                self.pos += 4;
+            } else if rest.starts_with("'''") {
+                self.pos += 3;
+            } else if rest.starts_with("''") {
+                self.pos += 2;
+                return;
+            } else if rest.starts_with("${") {
+                self.pos += 2;
+                self.skip_interpolation();
+            } else {
+                self.pos += rest.chars().next().map_or(1, char::len_utf8);
+            }
+        }
+    }
+
+    fn skip_interpolation(&mut self) {
+        let mut depth = 1;
+        while let Some(token) = self.next_token() {
+            match token.kind {
+                TokenKind::Open if token.text(self.source).starts_with('{') => depth += 1,
+                TokenKind::Close if token.text(self.source).starts_with('}') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn is_label_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+fn is_import_terminator(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '(' | ')' | '[' | ']' | '{' | '}' | '<' | '>' | ',')
+}
+
+fn is_path_start(rest: &str) -> bool {
+    let path_char = |offset: usize| {
+        rest[offset..]
+            .chars()
+            .next()
+            .is_some_and(|c| !is_import_terminator(c) && c != '/' && c != '\\')
+    };
+    if rest.starts_with("./") || rest.starts_with("~/") {
+        path_char(2)
+    } else if rest.starts_with("../") {
+        path_char(3)
+    } else {
+        rest.starts_with('/') && path_char(1)
+    }
+}
+
+/// A top-level `let` binding.
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub name: String,
+    pub annotation: Option<Range<usize>>,
+    pub value: Range<usize>,
+    pub span: Range<usize>,
+}
+
+/// The top-level `let ... in ...` chain of a Dhall file.
+#[derive(Debug, Clone, Default)]
+pub struct LetChain {
+    pub bindings: Vec<Binding>,
+    pub body: Range<usize>,
+}
+
+pub fn let_chain(source: &str) -> LetChain {
+    let tokens = code_tokens(source);
+    let mut chain = LetChain::default();
+    let mut ix = 0;
+
+    while ix < tokens.len() && tokens[ix].is(source, "let") {
+        let start = tokens[ix].span.start;
+        let Some(name) = tokens.get(ix + 1) else {
+            break;
+        };
+        ix += 2;
+
+        let mut annotation = None;
+        if tokens.get(ix).is_some_and(|token| token.is(source, ":")) {
+            let end = find_top_level(&tokens, ix + 1, |token| token.is(source, "="));
+            annotation = Some(span_between(&tokens, ix + 1, end));
+            ix = end;
+        }
+        if !tokens.get(ix).is_some_and(|token| token.is(source, "=")) {
+            break;
+        }
+
+        let end = end_of_binding_value(source, &tokens, ix + 1);
+        chain.bindings.push(Binding {
+            name: name.text(source).trim_matches('`').to_string(),
+            annotation,
+            value: span_between(&tokens, ix + 1, end),
+            span: start..tokens.get(end - 1).map_or(source.len(), |t| t.span.end),
+        });
+        ix = end;
+        if tokens.get(ix).is_some_and(|token| token.is(source, "in")) {
+            ix += 1;
+        }
+    }
+
+    chain.body = tokens.get(ix).map_or(source.len(), |t| t.span.start)..source.len();
+    chain
+}
+
+/// Returns the index of the first token at bracket depth zero (starting from
+/// `start`) matching `predicate`, or `tokens.len()` if there is none.
+pub fn find_top_level(tokens: &[Token], start: usize, predicate: impl Fn(&Token) -> bool) -> usize {
+    let mut depth = 0usize;
+    for (ix, token) in tokens.iter().enumerate().skip(start) {
+        match token.kind {
+            TokenKind::Open => depth += 1,
+            TokenKind::Close => depth = depth.saturating_sub(1),
+            _ if depth == 0 && predicate(token) => return ix,
+            _ => {}
+        }
+    }
+    tokens.len()
+}
+
+/// Returns the index of the token closing the bracket opened at `open`.
+pub fn matching_close(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0usize;
+    for (ix, token) in tokens.iter().enumerate().skip(open) {
+        match token.kind {
+            TokenKind::Open => depth += 1,
+            TokenKind::Close => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    return ix;
+                }
+            }
+            _ => {}
+        }
+    }
+    tokens.len().saturating_sub(1)
+}
+
+fn end_of_binding_value(source: &str, tokens: &[Token], start: usize) -> usize {
+    let mut depth = 0usize;
+    let mut nested_lets = 0usize;
+    let mut after_in = false;
+    for (ix, token) in tokens.iter().enumerate().skip(start) {
+        let text = token.text(source);
+        match token.kind {
+            TokenKind::Open => depth += 1,
+            TokenKind::Close => depth = depth.saturating_sub(1),
+            _ if depth > 0 => {}
+            _ if text == "let" => {
+                if nested_lets == 0 && !after_in && ix > start {
+                    return ix;
+                }
+                if nested_lets == 0 || after_in {
+                    nested_lets += 1;
+                }
+            }
+            _ if text == "in" => {
+                if nested_lets == 0 {
+                    return ix;
+                }
+                nested_lets -= 1;
+                after_in = true;
+                continue;
+            }
+            _ => {}
+        }
+        after_in = false;
+    }
+    tokens.len()
+}
+
+/// Returns the source range covering `tokens[start..end]`.
+pub fn span_between(tokens: &[Token], start: usize, end: usize) -> Range<usize> {
+    match (tokens.get(start), tokens.get(end.wrapping_sub(1))) {
+        (Some(first), Some(last)) if start < end => first.span.start..last.span.end,
+        _ => 0..0,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolStyle {
+    Ascii,
+    Unicode,
+}
+
+const SYMBOLS: &[(&str, &str)] = &[
+    ("\\", "λ"),
+    ("->", "→"),
+    ("forall", "∀"),
+    ("===", "≡"),
+    ("//\\\\", "⩓"),
+    ("/\\", "∧"),
+    ("//", "⫽"),
+];
+
+/// Rewrites keywords and operators to the given style, leaving text
+/// literals and comments untouched.
+pub fn convert_symbols(source: &str, style: SymbolStyle) -> String {
+    let mut output = String::with_capacity(source.len());
+    let mut last = 0;
+    for token in tokenize(source) {
+        if !matches!(token.kind, TokenKind::Symbol | TokenKind::Label) {
+            continue;
+        }
+        let text = token.text(source);
+        let replacement = SYMBOLS.iter().find_map(|(ascii, unicode)| match style {
+            SymbolStyle::Ascii if text == *unicode => Some(*ascii),
+            SymbolStyle::Unicode if text == *ascii => Some(*unicode),
+            _ => None,
+        });
+        if let Some(replacement) = replacement {
+            output.push_str(&source[last..token.span.start]);
+            output.push_str(replacement);
+            last = token.span.end;
+        }
+    }
+    output.push_str(&source[last..]);
+    output
+}
+
+/// Returns the text of a `{-| … -}` or `--| …` documentation comment.
+pub fn doc_comment_text(comment: &str) -> Option<String> {
+    if let Some(body) = comment.strip_prefix("{-|") {
+        let body = body.strip_suffix("-}").unwrap_or(body);
+        return Some(dedent(body));
+    }
+    let body = comment
+        .strip_prefix("--|")
+        .or_else(|| comment.strip_prefix("-- |"))?;
+    Some(body.trim().to_string())
+}
+
+/// Returns the documentation comments immediately preceding `offset`.
+pub fn doc_comment_before(source: &str, offset: usize) -> Option<String> {
+    let tokens = tokenize(source);
+    let end = tokens.partition_point(|token| token.span.end <= offset);
+    let mut lines = Vec::new();
+    for token in tokens[..end].iter().rev() {
+        if token.kind != TokenKind::Comment {
+            break;
+        }
+        match doc_comment_text(token.text(source)) {
+            Some(text) => lines.push(text),
+            None => break,
+        }
+    }
+    lines.reverse();
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+/// Returns the documentation comment at the top of a file, if any. Adjacent
+/// `--|` line comments are joined together.
+pub fn leading_doc_comment(source: &str) -> Option<String> {
+    let mut lines = Vec::new();
+    for token in tokenize(source) {
+        if token.kind != TokenKind::Comment {
+            break;
+        }
+        match doc_comment_text(token.text(source)) {
+            Some(text) => lines.push(text),
+            None if lines.is_empty() => continue,
+            None => break,
+        }
+    }
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+fn dedent(text: &str) -> String {
+    let indent = text
+        .lines()
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    text.lines()
+        .enumerate()
+        .map(|(ix, line)| {
+            if ix == 0 {
+                line.trim()
+            } else {
+                line.get(indent..).unwrap_or(line.trim())
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(source)
+            .into_iter()
+            .map(|token| (token.kind, token.text(source)))
+            .collect()
+    }
+
+    #[test]
+    fn tokenizes_comments_text_and_imports() {
+        let source =
+            "-- note\n{- block {- nested -} -}\nlet x = \"a -- b\" in ./x.dhall sha256:abc";
+        assert_eq!(
+            kinds(source),
+            vec![
+                (TokenKind::Comment, "-- note"),
+                (TokenKind::Comment, "{- block {- nested -} -}"),
+                (TokenKind::Label, "let"),
+                (TokenKind::Label, "x"),
+                (TokenKind::Symbol, "="),
+                (TokenKind::Text, "\"a -- b\""),
+                (TokenKind::Label, "in"),
+                (TokenKind::Path, "./x.dhall"),
+                (TokenKind::Hash, "sha256:abc"),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenizes_urls_env_imports_and_brackets() {
+        let source = "{ a = https://example.com/a.dhall, b = env:HOME as Text, c = [ 1 ] }";
+        let tokens = kinds(source);
+        assert!(tokens.contains(&(TokenKind::Url, "https://example.com/a.dhall")));
+        assert!(tokens.contains(&(TokenKind::Env, "env:HOME")));
+        assert!(tokens.contains(&(TokenKind::Number, "1")));
+        assert_eq!(tokens.first(), Some(&(TokenKind::Open, "{")));
+        assert_eq!(tokens.last(), Some(&(TokenKind::Close, "}")));
+    }
+
+    #[test]
+    fn tokenizes_multiline_text_with_interpolation() {
+        let source = "''\n  a ${\"b\"} '''c\n  ''";
+        assert_eq!(kinds(source), vec![(TokenKind::Text, source)]);
+    }
+
+    #[test]
+    fn code_tokens_drop_comments() {
+        let source = "1 -- one\n+ 2";
+        let tokens = code_tokens(source);
+        let texts: Vec<&str> = tokens.iter().map(|token| token.text(source)).collect();
+        assert_eq!(texts, vec!["1", "+", "2"]);
+    }
+
+    #[test]
+    fn let_chain_finds_bindings_and_body() {
+        let source = "let a : Natural = 1\nlet `b c` = { x = a }\nin a + 1";
+        let chain = let_chain(source);
+        let names: Vec<&str> = chain.bindings.iter().map(|b| b.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b c"]);
+        let a = &chain.bindings[0];
+        assert_eq!(
+            a.annotation.clone().map(|span| &source[span]),
+            Some("Natural")
+        );
+        assert_eq!(&source[a.value.clone()], "1");
+        assert_eq!(&source[chain.bindings[1].value.clone()], "{ x = a }");
+        assert_eq!(&source[chain.body.clone()], "a + 1");
+    }
+
+    #[test]
+    fn let_chain_stops_at_nested_lets() {
+        let source = "let a = let b = 1 in b\nin a";
+        let chain = let_chain(source);
+        assert_eq!(chain.bindings.len(), 1);
+        assert_eq!(&source[chain.bindings[0].value.clone()], "let b = 1 in b");
+        assert_eq!(&source[chain.body.clone()], "a");
+    }
+
+    #[test]
+    fn let_chain_without_bindings_is_all_body() {
+        let source = "{ a = 1 }";
+        let chain = let_chain(source);
+        assert!(chain.bindings.is_empty());
+        assert_eq!(&source[chain.body.clone()], source);
+    }
+}
@@ -0,0 +1,161 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::path::{Path, PathBuf};
+
+use zed_extension_api::{self as zed, Result, SlashCommandOutput, SlashCommandOutputSection};
+
+use crate::{cli, syntax, worktree};
+
+pub const SLASH_COMMAND: &str = "dhall-test";
+
+pub struct Assertion {
+    pub name: String,
+    pub program: String,
+}
+
+pub enum Outcome {
+    Passed,
+    Failed(String),
+}
+
+pub struct TestResult {
+    pub name: String,
+    pub outcome: Outcome,
+}
+
+/// Extracts every `assert` in the file's top-level `let` chain.
+///
+/// Each assertion is turned into a standalone program containing the
+/// non-assertion bindings that precede it, so that a failure in one
+/// assertion doesn't mask the results of the others.
+pub fn assertions(source: &str) -> Vec<Assertion> {
+    let chain = syntax::let_chain(source);
+    let is_assert = |text: &str| text.trim_start().starts_with("assert");
+
+    let mut assertions = Vec::new();
+    let mut prelude = String::new();
+    for binding in &chain.bindings {
+        let value = &source[binding.value.clone()];
+        if is_assert(value) {
+            assertions.push(Assertion {
+                name: binding.name.clone(),
+                program: format!("{prelude}in {value}\n"),
+            });
+        } else {
+            prelude.push_str(&source[binding.span.clone()]);
+            prelude.push('\n');
+        }
+    }
+
+    let body = source[chain.body.clone()].trim();
+    if is_assert(body) {
+        let program = if chain.bindings.is_empty() {
+            format!("{body}\n")
+        } else {
+            format!("{prelude}in {body}\n")
+        };
+        assertions.push(Assertion {
+            name: "<body>".to_string(),
+            program,
+        });
+    }
+    assertions
+}
+
+pub fn run_assertions(dhall: &str, cwd: &Path, source: &str) -> Result<Vec<TestResult>> {
+    assertions(source)
+        .into_iter()
+        .map(|assertion| {
+            let output = cli::run(dhall, &["type"], cwd, Some(&assertion.program))?;
+            let outcome = if output.success {
+                Outcome::Passed
+            } else {
+                Outcome::Failed(output.stderr.trim().to_string())
+            };
+            Ok(TestResult {
+                name: assertion.name,
+                outcome,
+            })
+        })
+        .collect()
+}
+
+/// Finds the `*Test*.dhall` files beneath `root`, relative to `root`.
+pub fn test_files(root: &Path) -> Vec<String> {
+    worktree::dhall_files(root)
+        .into_iter()
+        .filter(|path| {
+            Path::new(path)
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().contains("Test"))
+        })
+        .collect()
+}
+
+pub fn run_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let dhall = cli::which(worktree, "dhall")?;
+    let root = PathBuf::from(worktree.root_path());
+    let files = if args.is_empty() {
+        test_files(&root)
+    } else {
+        args
+    };
+    if files.is_empty() {
+        return Err("no *Test*.dhall files found in the worktree".to_string());
+    }
+
+    let mut text = String::new();
+    let mut sections = Vec::new();
+    let (mut passed, mut failed) = (0, 0);
+    for file in files {
+        let source = worktree.read_text_file(&file)?;
+        let cwd = root
+            .join(&file)
+            .parent()
+            .map_or(root.clone(), Path::to_path_buf);
+        let start = text.len();
+        text.push_str(&format!("{file}\n"));
+        for result in run_assertions(&dhall, &cwd, &source)? {
+            match result.outcome {
+                Outcome::Passed => {
+                    passed += 1;
+                    text.push_str(&format!("  ✓ {}\n", result.name));
+                }
+                Outcome::Failed(error) => {
+                    failed += 1;
+                    text.push_str(&format!("  ✗ {}\n", result.name));
+                    for line in error.lines() {
+                        text.push_str(&format!("      {line}\n"));
+                    }
+                }
+            }
+        }
+        sections.push(SlashCommandOutputSection {
+            range: (start..text.len()).into(),
+            label: file,
+        });
+    }
+    text.push_str(&format!(
+        "\n{} assertions, {passed} passed, {failed} failed\n",
+        passed + failed
+    ));
+
+    Ok(SlashCommandOutput { text, sections })
+}
@@ -0,0 +1,916 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use zed_extension_api::{
+    self as zed,
+    http_client::{HttpMethod, HttpRequest, RedirectPolicy},
+    Result, SlashCommandOutput, SlashCommandOutputSection,
+};
+
+use crate::language_server::DhallLanguageServer;
+use crate::settings::DhallSettings;
+use crate::{cli, preamble, prelude};
+
+pub const SLASH_COMMAND: &str = "dhall-version";
+
+/// The tools `/dhall-version` reports on, with the release asset each comes
+/// from.
+const REPORTED_TOOLS: &[(&str, &str)] = &[
+    ("dhall", "dhall"),
+    ("dhall-lsp-server", "dhall-lsp-server"),
+    ("dhall-json", "dhall-to-json"),
+];
+
+/// Returns the suffix of the dhall-haskell release assets built for this
+/// platform, and how each is packaged.
+pub fn asset_suffix() -> Result<(&'static str, zed::DownloadedFileType)> {
+    match zed::current_platform() {
+        (zed::Os::Mac, zed::Architecture::Aarch64) => Ok((
+            "aarch64-darwin.tar.bz2",
+            zed::DownloadedFileType::Uncompressed,
+        )),
+        (zed::Os::Mac, zed::Architecture::X8664) => Ok((
+            "x86_64-darwin.tar.bz2",
+            zed::DownloadedFileType::Uncompressed,
+        )),
+        (zed::Os::Linux, zed::Architecture::X8664) => Ok((
+            "x86_64-linux.tar.bz2",
+            zed::DownloadedFileType::Uncompressed,
+        )),
+        (zed::Os::Windows, zed::Architecture::X8664) => {
+            Ok(("x86_64-windows.zip", zed::DownloadedFileType::Zip))
+        }
+        (platform, arch) => Err(format!(
+            "unsupported platform/arch combination: {platform:?}/{arch:?}"
+        )),
+    }
+}
+
+/// Where install timings are logged, in the extension's working directory.
+const INSTALL_LOG: &str = "install.log";
+
+/// Whether the most recently read settings asked for install timings.
+static LOG_TIMINGS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_log_timings(enabled: bool) {
+    LOG_TIMINGS.store(enabled, Ordering::Relaxed);
+}
+
+/// Runs one phase of an install, appending how long it took (and whether it
+/// failed) to `install.log` if timings are enabled, e.g. to attach to a
+/// report that Zed is slow to open Dhall projects. Install locks are beaten
+/// between phases.
+fn timed<T>(phase: &str, run: impl FnOnce() -> Result<T>) -> Result<T> {
+    heartbeat();
+    let started = Instant::now();
+    let result = run();
+    heartbeat();
+    if !LOG_TIMINGS.load(Ordering::Relaxed) {
+        return result;
+    }
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs());
+    let line = format!(
+        "{now} {phase}: {} ms{}\n",
+        started.elapsed().as_millis(),
+        if result.is_ok() { "" } else { " (failed)" }
+    );
+    if let Ok(mut log) = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(INSTALL_LOG)
+    {
+        log.write_all(line.as_bytes()).ok();
+    }
+    result
+}
+
+/// Returns the newest release of dhall-haskell.
+pub fn latest_release() -> Result<zed::GithubRelease> {
+    timed("release lookup", || {
+        zed::latest_github_release(
+            "dhall-lang/dhall-haskell",
+            zed::GithubReleaseOptions {
+                require_assets: true,
+                pre_release: false,
+            },
+        )
+    })
+}
+
+/// The file at the root of a worktree that pins the dhall-haskell release
+/// installed for it, e.g. `1.41.2`.
+const VERSION_FILE: &str = ".dhall-version";
+
+/// Lists, in a release directory, the roots of the worktrees that pin it, so
+/// that it isn't removed as stale when a newer release is installed.
+const PINNED_MARKER: &str = ".pinned";
+
+/// Returns the dhall-haskell release the worktree pins, from the
+/// `dhall_haskell_version` setting (e.g. in the project's
+/// `.zed/settings.json`) or else its `.dhall-version` file.
+pub fn pinned_version(worktree: &zed::Worktree) -> Result<Option<String>> {
+    let version = DhallSettings::for_worktree(worktree)?
+        .dhall_haskell_version
+        .or_else(|| worktree.read_text_file(VERSION_FILE).ok());
+    Ok(version
+        .map(|version| version.trim().trim_start_matches('v').to_string())
+        .filter(|version| !version.is_empty()))
+}
+
+/// Returns the given release of dhall-haskell, or the newest.
+pub fn release(version: Option<&str>) -> Result<zed::GithubRelease> {
+    match version {
+        Some(version) => timed("release lookup", || {
+            zed::github_release_by_tag_name("dhall-lang/dhall-haskell", version)
+                .map_err(|e| format!("no dhall-haskell release {version}: {e}"))
+        }),
+        None => latest_release(),
+    }
+}
+
+/// The worktree roots that pin the release in `release_dir`, one per line
+/// of its marker.
+fn pinned_by(release_dir: &str) -> Vec<String> {
+    fs::read_to_string(Path::new(release_dir).join(PINNED_MARKER))
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn set_pinned_by(release_dir: &str, roots: &[String]) {
+    let marker = Path::new(release_dir).join(PINNED_MARKER);
+    if roots.is_empty() {
+        fs::remove_file(marker).ok();
+    } else {
+        fs::write(marker, roots.join("\n") + "\n").ok();
+    }
+}
+
+/// Records that the worktree at `root` pins the release in `release_dir`,
+/// keeping it from being removed as stale, and no longer pins any other.
+pub fn mark_pinned(release_dir: &str, root: &str) {
+    unpin(root);
+    let mut roots = pinned_by(release_dir);
+    roots.push(root.to_string());
+    set_pinned_by(release_dir, &roots);
+}
+
+/// Records that the worktree at `root` pins no release, so that those it
+/// pinned before can be removed as stale once no other worktree pins them.
+pub fn unpin(root: &str) {
+    for release in installed_releases() {
+        let mut roots = pinned_by(&release);
+        if roots.iter().any(|pinned| pinned == root) {
+            roots.retain(|pinned| pinned != root);
+            set_pinned_by(&release, &roots);
+        }
+    }
+}
+
+/// Returns the asset of `release` that packages `package` (e.g.
+/// `dhall-json`) for this platform.
+pub fn release_asset<'a>(
+    release: &'a zed::GithubRelease,
+    package: &str,
+) -> Result<&'a zed::GithubReleaseAsset> {
+    let (file_suffix, _) = asset_suffix()?;
+    release
+        .assets
+        .iter()
+        .find(|asset| {
+            asset.name.starts_with(&format!("{package}-")) && asset.name.ends_with(file_suffix)
+        })
+        .ok_or_else(|| format!("no asset found matching {package}-*-{file_suffix}"))
+}
+
+/// Checks that the extension's working directory can be written to, which
+/// isn't the case on some locked-down machines. Installing is then
+/// impossible, and only the tools on the `PATH` can be used.
+pub fn check_writable() -> Result<()> {
+    let probe = ".write-test";
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(probe)
+        .map(|_| {
+            fs::remove_file(probe).ok();
+        })
+        .map_err(|e| {
+            let dir = env::current_dir()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_else(|_| "the extension directory".to_string());
+            format!(
+                "automatic installation is disabled because {dir} is read-only ({e}); \
+                 install the dhall-haskell tools on the PATH instead"
+            )
+        })
+}
+
+/// The free space an install needs: the archive and its extracted contents.
+const REQUIRED_SPACE_MB: u64 = 300;
+
+/// Checks that the working directory has room for an install, so that a
+/// full disk is reported up front rather than as a failure partway through
+/// extraction. The space is found with `df`, so the check is skipped where
+/// that isn't available (e.g. on Windows).
+fn check_free_space() -> Result<()> {
+    let Ok(dir) = env::current_dir() else {
+        return Ok(());
+    };
+    let Ok(output) = std::process::Command::new("df")
+        .arg("-Pk")
+        .arg(&dir)
+        .output()
+    else {
+        return Ok(());
+    };
+    // The available space in KiB is the fourth column of the second line.
+    let available_kb = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .nth(1)
+        .and_then(|line| line.split_whitespace().nth(3))
+        .and_then(|available| available.parse::<u64>().ok());
+    match available_kb {
+        Some(kb) if kb / 1024 < REQUIRED_SPACE_MB => Err(format!(
+            "need {REQUIRED_SPACE_MB} MB free at {} to install, but only {} MB is available",
+            dir.display(),
+            kb / 1024
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// How long an install's lock may go without a heartbeat before the install
+/// is presumed to have been interrupted (e.g. Zed was killed) and another
+/// takes over. Installs beat while they download and between phases.
+const LOCK_STALE: Duration = Duration::from_secs(30);
+
+/// How often a held lock's heartbeat is written.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A lock held by this instance, for `heartbeat`.
+struct Heartbeat {
+    path: String,
+    token: String,
+    beat_at: Instant,
+}
+
+static HELD: Mutex<Vec<Heartbeat>> = Mutex::new(Vec::new());
+
+/// Marks `package_dir` as being installed, so that a second request for the
+/// same binary (e.g. from another Zed window sharing the working directory)
+/// waits for this install instead of starting its own. The lock file holds
+/// a token identifying the install, rewritten as a heartbeat, so that a
+/// live install is told apart from one that was interrupted. Removed when
+/// dropped.
+struct InstallLock {
+    path: String,
+    token: String,
+}
+
+impl InstallLock {
+    fn path(package_dir: &str) -> String {
+        format!("{package_dir}.lock")
+    }
+
+    /// Whether an install of `package_dir` holds a lock with a recent
+    /// heartbeat.
+    fn is_held(package_dir: &str) -> bool {
+        fs::metadata(Self::path(package_dir))
+            .and_then(|stat| stat.modified())
+            .is_ok_and(|modified| {
+                SystemTime::now()
+                    .duration_since(modified)
+                    .map_or(true, |age| age < LOCK_STALE)
+            })
+    }
+
+    /// Takes the lock, or returns `None` once another install holding it has
+    /// finished.
+    fn acquire(package_dir: &str) -> Result<Option<Self>> {
+        let path = Self::path(package_dir);
+        if let Some(parent) = Path::new(&path).parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+        }
+        let token = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |now| now.as_nanos())
+            .to_string();
+        let mut waited = false;
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) if waited => {
+                    fs::remove_file(&path).ok();
+                    return Ok(None);
+                }
+                Ok(mut file) => {
+                    file.write_all(token.as_bytes())
+                        .map_err(|e| format!("failed to write {path}: {e}"))?;
+                    if let Ok(mut held) = HELD.lock() {
+                        held.push(Heartbeat {
+                            path: path.clone(),
+                            token: token.clone(),
+                            beat_at: Instant::now(),
+                        });
+                    }
+                    return Ok(Some(Self { path, token }));
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    if Self::is_held(package_dir) {
+                        thread::sleep(Duration::from_secs(1));
+                        waited = true;
+                    } else {
+                        fs::remove_file(&path).ok();
+                        waited = false;
+                    }
+                }
+                Err(e) => return Err(format!("failed to create {path}: {e}")),
+            }
+        }
+    }
+
+    /// Whether the lock is still this install's, and not taken over after
+    /// its heartbeat lapsed.
+    fn is_owned(&self) -> bool {
+        fs::read_to_string(&self.path).is_ok_and(|token| token == self.token)
+    }
+}
+
+impl Drop for InstallLock {
+    fn drop(&mut self) {
+        if let Ok(mut held) = HELD.lock() {
+            held.retain(|heartbeat| heartbeat.token != self.token);
+        }
+        if self.is_owned() {
+            fs::remove_file(&self.path).ok();
+        }
+    }
+}
+
+/// Rewrites the locks this instance holds, if they haven't been rewritten
+/// in a while, to show that their installs are still running.
+fn heartbeat() {
+    let Ok(mut held) = HELD.lock() else {
+        return;
+    };
+    for heartbeat in held.iter_mut() {
+        if heartbeat.beat_at.elapsed() < HEARTBEAT_INTERVAL {
+            continue;
+        }
+        heartbeat.beat_at = Instant::now();
+        if fs::read_to_string(&heartbeat.path).is_ok_and(|token| token == heartbeat.token) {
+            fs::write(&heartbeat.path, &heartbeat.token).ok();
+        }
+    }
+}
+
+/// Whether an install in this instance still holds the lock with `token`.
+fn is_beating(token: &str) -> bool {
+    HELD.lock()
+        .is_ok_and(|held| held.iter().any(|heartbeat| heartbeat.token == token))
+}
+
+/// Downloads `asset` and extracts it into `package_dir`, returning the
+/// absolute path of `binary_name` in it. Both the language server and the
+/// other tools are installed this way. Concurrent installs of the same
+/// package are coalesced into one download.
+pub fn install(
+    asset: &zed::GithubReleaseAsset,
+    package_dir: &str,
+    binary_name: &str,
+) -> Result<String> {
+    check_writable()?;
+    check_free_space()?;
+    let Some(lock) = InstallLock::acquire(package_dir)? else {
+        if let Some(path) = installed_binary(package_dir, binary_name) {
+            return Ok(path);
+        }
+        // The other install failed, so try again.
+        return install(asset, package_dir, binary_name);
+    };
+    fs::create_dir_all(package_dir).map_err(|e| format!("failed to create {package_dir}: {e}"))?;
+
+    // Extracting into a staging directory means a failed install leaves
+    // nothing behind that looks installed. A partial download is kept (as
+    // `<asset>.part`) to be resumed.
+    // Each install stages separately, in case one whose heartbeat lapsed is
+    // still running. The leftovers of installs whose locks went stale are
+    // removed, but not those of installs this instance is still beating for.
+    for entry in fs::read_dir(package_dir).into_iter().flatten().flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Some(token) = name.strip_prefix(".extracting") else {
+            continue;
+        };
+        if !is_beating(token.trim_start_matches('-')) {
+            fs::remove_dir_all(entry.path()).ok();
+        }
+    }
+    let staging = format!("{package_dir}/.extracting-{}", lock.token);
+    let result = extract_asset(asset, package_dir, &staging).and_then(|()| {
+        let path = installed_binary(&staging, binary_name)
+            .ok_or_else(|| format!("{} does not contain {binary_name}", asset.name))?;
+        timed(&format!("verify {binary_name}"), || {
+            ensure_executable(&path)
+        })
+    });
+    if let Err(e) = result {
+        fs::remove_dir_all(&staging).ok();
+        return Err(e);
+    }
+    if !lock.is_owned() {
+        // Another install took over; wait for it instead.
+        fs::remove_dir_all(&staging).ok();
+        drop(lock);
+        return install(asset, package_dir, binary_name);
+    }
+
+    let entries = fs::read_dir(&staging).map_err(|e| format!("failed to list {staging}: {e}"))?;
+    for entry in entries.flatten() {
+        let target = Path::new(package_dir).join(entry.file_name());
+        fs::rename(entry.path(), &target)
+            .map_err(|e| format!("failed to move {}: {e}", target.display()))?;
+    }
+    fs::remove_dir_all(&staging).ok();
+    installed_binary(package_dir, binary_name)
+        .ok_or_else(|| format!("{} does not contain {binary_name}", asset.name))
+}
+
+/// Downloads `asset` into `package_dir` and extracts it into `staging`.
+fn extract_asset(asset: &zed::GithubReleaseAsset, package_dir: &str, staging: &str) -> Result<()> {
+    let (_, download_type) = asset_suffix()?;
+    if download_type == zed::DownloadedFileType::Uncompressed {
+        // These are .tar.bz2, which Zed can't extract.
+        fs::create_dir_all(staging).map_err(|e| format!("failed to create {staging}: {e}"))?;
+        let download_path = format!("{package_dir}/{}", asset.name);
+        timed(&format!("download {}", asset.name), || {
+            download_archive(asset, &download_path)
+        })?;
+        let result = timed(&format!("extract {}", asset.name), || {
+            extract(&download_path, staging)
+        });
+        fs::remove_file(&download_path).ok();
+        result
+    } else {
+        timed(&format!("download and extract {}", asset.name), || {
+            zed::download_file(&asset.download_url, staging, download_type)
+                .map_err(|e| format!("failed to download file: {e}"))
+        })
+    }
+}
+
+/// Sets the executable bit on an extracted binary, which not every archive
+/// (or extractor) preserves, and checks that it runs, so that a broken
+/// install fails here rather than with an opaque error when Zed spawns it.
+fn ensure_executable(path: &str) -> Result<()> {
+    if zed::current_platform().0 != zed::Os::Windows {
+        let cwd =
+            env::current_dir().map_err(|e| format!("failed to get working directory: {e}"))?;
+        let relative = Path::new(path)
+            .strip_prefix(&cwd)
+            .unwrap_or(Path::new(path));
+        zed::make_file_executable(&relative.to_string_lossy())
+            .map_err(|e| format!("failed to make {path} executable: {e}"))?;
+    }
+    let dir = Path::new(path).parent().unwrap_or(Path::new("."));
+    let problem = match cli::run(path, &["--version"], dir, None) {
+        Ok(output) if output.success => return Ok(()),
+        Ok(output) => format!("`--version` failed: {}", output.stderr.trim()),
+        Err(e) => e,
+    };
+    Err(format!(
+        "{path} is not executable ({problem}); it may be built for another platform, \
+         or the directory may not allow running programs"
+    ))
+}
+
+/// How many times to try downloading an archive that arrives truncated or
+/// whose download is interrupted.
+const DOWNLOAD_ATTEMPTS: usize = 2;
+
+/// Downloads the `.tar.bz2` `asset` to `path`, checking that the whole
+/// archive arrived before it is extracted, and downloading it again if not.
+fn download_archive(asset: &zed::GithubReleaseAsset, path: &str) -> Result<()> {
+    let mut error = String::new();
+    for _ in 0..DOWNLOAD_ATTEMPTS {
+        fs::remove_file(path).ok();
+        if let Err(e) = download_resumable(&asset.download_url, path) {
+            error = e;
+            continue;
+        }
+        if is_complete_bzip2(path) {
+            return Ok(());
+        }
+        error = format!("truncated download of {}", asset.name);
+    }
+    fs::remove_file(path).ok();
+    Err(format!("{error} after {DOWNLOAD_ATTEMPTS} attempts"))
+}
+
+/// Downloads `url` to `path` by way of `<path>.part`, which is kept if the
+/// connection drops so that the next attempt (even in a later session)
+/// picks up where it left off with a range request. Without a length to
+/// check the result against, the download starts over instead.
+fn download_resumable(url: &str, path: &str) -> Result<()> {
+    let partial = format!("{path}.part");
+    let head = HttpRequest::builder()
+        .method(HttpMethod::Head)
+        .url(url)
+        .redirect_policy(RedirectPolicy::FollowAll)
+        .build()?
+        .fetch();
+    let header = |name: &str| {
+        head.as_ref().ok().and_then(|response| {
+            response
+                .headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.clone())
+        })
+    };
+    let Some(length) = header("content-length").and_then(|length| length.parse::<u64>().ok())
+    else {
+        fs::remove_file(&partial).ok();
+        return zed::download_file(url, path, zed::DownloadedFileType::Uncompressed)
+            .map_err(|e| format!("failed to download file: {e}"));
+    };
+
+    let mut offset = fs::metadata(&partial).map_or(0, |stat| stat.len());
+    if offset > length || header("accept-ranges").as_deref() != Some("bytes") {
+        offset = 0;
+    }
+    let mut request = HttpRequest::builder()
+        .method(HttpMethod::Get)
+        .url(url)
+        .redirect_policy(RedirectPolicy::FollowAll);
+    if offset > 0 {
+        request = request.header("Range", format!("bytes={offset}-"));
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(offset > 0)
+        .write(true)
+        .truncate(offset == 0)
+        .open(&partial)
+        .map_err(|e| format!("failed to create {partial}: {e}"))?;
+    let stream = request
+        .build()?
+        .fetch_stream()
+        .map_err(|e| format!("failed to download {url}: {e}"))?;
+    while let Some(chunk) = stream
+        .next_chunk()
+        .map_err(|e| format!("download of {url} interrupted: {e}"))?
+    {
+        file.write_all(&chunk)
+            .map_err(|e| format!("failed to write {partial}: {e}"))?;
+        heartbeat();
+    }
+    drop(file);
+
+    let size = fs::metadata(&partial).map_or(0, |stat| stat.len());
+    if size != length {
+        // The server may have ignored the range and sent the whole file.
+        fs::remove_file(&partial).ok();
+        return Err(format!(
+            "download of {url} has {size} bytes instead of {length}"
+        ));
+    }
+    fs::rename(&partial, path).map_err(|e| format!("failed to move {partial}: {e}"))
+}
+
+/// Checks the file at `path` for the `BZh` signature at the start and the
+/// 48-bit end-of-stream marker before the final CRC (and up to 7 bits of
+/// padding) at the end, which a download cut short won't have. Only those
+/// bytes are read.
+fn is_complete_bzip2(path: &str) -> bool {
+    const END_OF_STREAM: u64 = 0x1772_4538_5090;
+    let mut head = [0u8; 3];
+    let mut tail = [0u8; 11];
+    let read = File::open(path).and_then(|mut file| {
+        file.read_exact(&mut head)?;
+        file.seek(SeekFrom::End(-(tail.len() as i64)))?;
+        file.read_exact(&mut tail)
+    });
+    if read.is_err() || &head != b"BZh" {
+        return false;
+    }
+    let bit = |ix: usize| (tail[ix / 8] >> (7 - ix % 8)) & 1;
+    let total = tail.len() * 8;
+    (0..8).any(|padding| {
+        let start = total - padding - 32 - 48;
+        let marker = (start..start + 48).fold(0u64, |acc, ix| (acc << 1) | u64::from(bit(ix)));
+        marker == END_OF_STREAM
+    })
+}
+
+/// The programs that can extract a `.tar.bz2` archive into a directory, in
+/// order of preference, for systems without a usable `tar`.
+const EXTRACTORS: &[(&str, &[&str])] = &[
+    ("tar", &["-xf", "{archive}", "-C", "{dir}"]),
+    ("bsdtar", &["-xf", "{archive}", "-C", "{dir}"]),
+    ("gtar", &["-xf", "{archive}", "-C", "{dir}"]),
+    ("busybox", &["tar", "-xjf", "{archive}", "-C", "{dir}"]),
+    ("python3", &["-m", "tarfile", "-e", "{archive}", "{dir}"]),
+];
+
+/// Extracts the `.tar.bz2` archive at `archive` into `dir`. This is left to
+/// `tar` outside the sandbox, which decompresses the stream and writes each
+/// file as it reads it: the archives are around 100MB, too much to buffer
+/// in the extension's memory. Where there is no `tar`, the other
+/// `EXTRACTORS` are tried in turn.
+fn extract(archive: &str, dir: &str) -> Result<()> {
+    for (program, args) in EXTRACTORS {
+        let args = args
+            .iter()
+            .map(|arg| arg.replace("{archive}", archive).replace("{dir}", dir));
+        let exit_status = match std::process::Command::new(program).args(args).status() {
+            Ok(exit_status) => exit_status,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("failed to decompress {archive}: {e:?}")),
+        };
+        if !exit_status.success() {
+            return Err(format!(
+                "failed to decompress {archive} with {program}: status {exit_status:?}"
+            ));
+        }
+        return Ok(());
+    }
+    let tried: Vec<&str> = EXTRACTORS.iter().map(|(program, _)| *program).collect();
+    Err(format!(
+        "failed to decompress {archive}: none of {} is installed",
+        tried.join(", ")
+    ))
+}
+
+/// Locates `tool` (e.g. `json-to-dhall`), preferring the worktree's `$PATH`
+/// and otherwise installing the dhall-haskell release asset `package` (e.g.
+/// `dhall-json`) that contains it, from the release the worktree pins or
+/// else the newest.
+pub fn find(worktree: &zed::Worktree, package: &str, tool: &str) -> Result<String> {
+    let binary_name = cli::executable_name(tool);
+    if let Some(path) = cli::find_on_path(worktree, &binary_name) {
+        return Ok(path);
+    }
+
+    let pinned = pinned_version(worktree)?;
+    if let Some(version) = &pinned {
+        let package_dir = format!("dhall-haskell-{version}/{package}");
+        if let Some(path) = installed_binary(&package_dir, &binary_name) {
+            return Ok(path);
+        }
+    }
+    let release = release(pinned.as_deref())?;
+    let asset = release_asset(&release, package)?;
+    let release_dir = format!("dhall-haskell-{}", release.version);
+    let package_dir = format!("{release_dir}/{package}");
+    if let Some(path) = installed_binary(&package_dir, &binary_name) {
+        return Ok(path);
+    }
+    let path = install(asset, &package_dir, &binary_name)?;
+    match pinned {
+        Some(_) => mark_pinned(&release_dir, &worktree.root_path()),
+        None => unpin(&worktree.root_path()),
+    }
+    Ok(path)
+}
+
+/// Returns the absolute path of an extracted binary, which is under `bin/`
+/// in the tarballs and at the top level of the Windows archives.
+pub fn installed_binary(package_dir: &str, binary_name: &str) -> Option<String> {
+    let package_dir = env::current_dir().ok()?.join(package_dir);
+    [package_dir.join("bin"), package_dir]
+        .iter()
+        .map(|dir| dir.join(binary_name))
+        .find(|path| path.is_file())
+        .map(|path| path.to_string_lossy().into_owned())
+}
+
+/// Returns the dhall-haskell releases installed in the working directory,
+/// newest first.
+fn installed_releases() -> Vec<String> {
+    let mut releases: Vec<String> = fs::read_dir(".")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            name.starts_with("dhall-haskell-").then_some(name)
+        })
+        .collect();
+    releases
+        .sort_by_key(|release| prelude::version_key(release.trim_start_matches("dhall-haskell-")));
+    releases.reverse();
+    releases
+}
+
+/// Returns the dhall-haskell release, e.g. `dhall-haskell-1.42.1`, that the
+/// installed binary at `path` belongs to.
+pub fn release_of(path: &str) -> Option<String> {
+    let cwd = env::current_dir().ok()?;
+    let path = Path::new(path);
+    let release = path
+        .strip_prefix(&cwd)
+        .unwrap_or(path)
+        .components()
+        .next()?;
+    let release = release.as_os_str().to_string_lossy();
+    release
+        .starts_with("dhall-haskell-")
+        .then(|| release.into_owned())
+}
+
+/// Removes all but the newest `retained` dhall-haskell releases (at least
+/// one), leaving releases that a worktree pins, the release of the `running`
+/// language server's binary, and the rest of the working directory alone.
+pub fn remove_stale_releases(retained: usize, running: Option<&str>) {
+    let running = running.and_then(release_of);
+    let unpinned = installed_releases()
+        .into_iter()
+        .filter(|release| pinned_by(release).is_empty());
+    let stale = unpinned
+        .skip(retained.max(1))
+        .filter(|release| Some(release) != running.as_ref());
+    for release in stale {
+        fs::remove_dir_all(release).ok();
+    }
+}
+
+/// Returns the newest installed copy of `tool` from the release asset
+/// `package`, without downloading anything.
+pub fn installed(package: &str, tool: &str) -> Option<String> {
+    installed_releases()
+        .iter()
+        .find_map(|release| installed_in(release, package, tool))
+}
+
+/// Returns the copy of `tool` from the release asset `package` installed in
+/// `release_dir`. The language server is extracted straight into the
+/// release's directory, and other packages into a subdirectory of it.
+pub fn installed_in(release_dir: &str, package: &str, tool: &str) -> Option<String> {
+    let binary_name = cli::executable_name(tool);
+    // A package that is still being extracted isn't ready to run.
+    let finished = |package_dir: &str| {
+        if InstallLock::is_held(package_dir) {
+            return None;
+        }
+        installed_binary(package_dir, &binary_name)
+    };
+    finished(&format!("{release_dir}/{package}")).or_else(|| finished(release_dir))
+}
+
+/// Reports which of `dhall`, `dhall-lsp-server` and `dhall-to-json` are
+/// available, their versions, and whether each is on the `PATH` or was
+/// installed by the extension, e.g. for a bug report. `language_server` is
+/// the binary the language server was started with, if it has been. With
+/// `update`, the newest language server release is installed first, since
+/// the language server itself starts without checking for one.
+pub fn run_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+    mut language_server: Option<&str>,
+) -> Result<SlashCommandOutput> {
+    let update = match args.first().map(String::as_str) {
+        Some("update") => true,
+        Some(_) => return Err("usage: /dhall-version [update]".to_string()),
+        None => false,
+    };
+    let root = PathBuf::from(worktree.root_path());
+    let cwd = env::current_dir().map_err(|e| format!("failed to get working directory: {e}"))?;
+    let mut text = String::new();
+    let lsp_on_path =
+        cli::find_on_path(worktree, &cli::executable_name("dhall-lsp-server")).is_some();
+    let pinned = pinned_version(worktree)?;
+    if update && lsp_on_path {
+        text.push_str("dhall-lsp-server is on the PATH, so it isn't updated.\n\n");
+    } else if let (true, Some(version)) = (update, &pinned) {
+        text.push_str(&format!(
+            "This worktree pins dhall-haskell {version}, so it isn't updated.\n\n"
+        ));
+    } else if update {
+        let retained = DhallSettings::for_worktree(worktree)?.retained_releases;
+        let path = DhallLanguageServer::install_release(
+            None,
+            None,
+            &worktree.root_path(),
+            retained,
+            language_server,
+        )?;
+        if language_server.is_some_and(|running| cwd.join(running) != Path::new(&path)) {
+            text.push_str(&format!(
+                "Installed {path}; restart the language server to use it.\n\n"
+            ));
+        }
+        language_server = None;
+    }
+    for (package, tool) in REPORTED_TOOLS {
+        let (path, source) = match cli::find_on_path(worktree, &cli::executable_name(tool)) {
+            Some(path) => (path, "PATH"),
+            None => {
+                let running = language_server
+                    .filter(|_| *tool == "dhall-lsp-server")
+                    .map(|path| cwd.join(path).to_string_lossy().into_owned());
+                let managed = match &pinned {
+                    Some(version) => {
+                        installed_in(&format!("dhall-haskell-{version}"), package, tool)
+                    }
+                    None => running.or_else(|| installed(package, tool)),
+                };
+                match managed {
+                    Some(path) => (path, "managed"),
+                    None => {
+                        text.push_str(&format!("- {tool}: not installed\n"));
+                        continue;
+                    }
+                }
+            }
+        };
+        let version = preamble::version(&path, &root).unwrap_or_else(|| "unknown".to_string());
+        text.push_str(&format!("- {tool} {version} ({source}: {path})\n"));
+    }
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: "Dhall toolchain".to_string(),
+        }],
+        text,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `hello\n`, whose end-of-stream marker happens to be byte-aligned.
+    const ALIGNED: &[u8] = &[
+        0x42, 0x5a, 0x68, 0x39, 0x31, 0x41, 0x59, 0x26, 0x53, 0x59, 0xc1, 0xc0, 0x80, 0xe2, 0x00,
+        0x00, 0x01, 0x41, 0x00, 0x00, 0x10, 0x02, 0x44, 0xa0, 0x00, 0x30, 0xcd, 0x00, 0xc3, 0x46,
+        0x29, 0x97, 0x17, 0x72, 0x45, 0x38, 0x50, 0x90, 0xc1, 0xc0, 0x80, 0xe2,
+    ];
+
+    /// `dhall ` fifty times, whose marker is followed by padding.
+    const PADDED: &[u8] = &[
+        0x42, 0x5a, 0x68, 0x39, 0x31, 0x41, 0x59, 0x26, 0x53, 0x59, 0x71, 0x76, 0xde, 0x5b, 0x00,
+        0x00, 0x4a, 0x91, 0x00, 0x40, 0x00, 0x24, 0x44, 0x20, 0x00, 0x20, 0xa5, 0x43, 0xd4, 0x19,
+        0xa0, 0xc8, 0xb2, 0x2e, 0xa2, 0xc8, 0xbc, 0x8b, 0x22, 0xf8, 0xbb, 0x92, 0x29, 0xc2, 0x84,
+        0x83, 0x8b, 0xb6, 0xf2, 0xd8,
+    ];
+
+    fn check(name: &str, bytes: &[u8]) -> bool {
+        let path = env::temp_dir().join(format!("dhall-bzip2-{name}-{}", std::process::id()));
+        fs::write(&path, bytes).unwrap();
+        let complete = is_complete_bzip2(&path.to_string_lossy());
+        fs::remove_file(path).ok();
+        complete
+    }
+
+    #[test]
+    fn accepts_complete_archives() {
+        assert!(check("aligned", ALIGNED));
+        assert!(check("padded", PADDED));
+    }
+
+    #[test]
+    fn rejects_truncated_archives() {
+        for len in [ALIGNED.len() - 1, ALIGNED.len() - 4, ALIGNED.len() / 2] {
+            assert!(!check("truncated", &ALIGNED[..len]), "{len} bytes");
+        }
+        assert!(!check("truncated", &PADDED[..PADDED.len() - 2]));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(!check("empty", b""));
+        assert!(!check("short", b"BZh"));
+        let mut gzip = ALIGNED.to_vec();
+        gzip[..3].copy_from_slice(&[0x1f, 0x8b, 0x08]);
+        assert!(!check("gzip", &gzip));
+    }
+}
@@ -0,0 +1,133 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use zed_extension_api::{self as zed, Result, SlashCommandOutput, SlashCommandOutputSection};
+
+use crate::docs::{self, Entry};
+use crate::syntax::{self, SymbolStyle};
+use crate::{builtins, prelude};
+
+pub const SLASH_COMMAND: &str = "dhall-search";
+
+/// Results scoring below this similarity are dropped.
+const MIN_SCORE: f64 = 0.6;
+const MAX_RESULTS: usize = 10;
+
+/// Reduces a type to a sequence of tokens in which type variables are
+/// renamed by order of appearance, so that `∀(a : Type) → List a` and
+/// `List x` compare equal.
+pub fn normalize(signature: &str) -> Vec<String> {
+    let source = syntax::convert_symbols(signature, SymbolStyle::Unicode);
+    let mut tokens: Vec<&str> = syntax::code_tokens(&source)
+        .iter()
+        .map(|token| token.text(&source))
+        .collect();
+
+    let mut variables: Vec<&str> = Vec::new();
+    while let ["∀", "(", name, ":", "Type", ")", "→", ..] = tokens.as_slice() {
+        variables.push(name);
+        tokens.drain(..7);
+    }
+
+    let mut renamed: Vec<&str> = Vec::new();
+    tokens
+        .into_iter()
+        .map(|token| {
+            let is_variable = variables.contains(&token)
+                || (token.starts_with(|c: char| c.is_ascii_lowercase())
+                    && builtins::lookup_keyword(token).is_none());
+            if !is_variable {
+                return token.to_string();
+            }
+            let ix = renamed
+                .iter()
+                .position(|name| *name == token)
+                .unwrap_or_else(|| {
+                    renamed.push(token);
+                    renamed.len() - 1
+                });
+            format!("#{ix}")
+        })
+        .collect()
+}
+
+fn similarity(a: &[String], b: &[String]) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in 0..a.len() {
+        for j in 0..b.len() {
+            lcs[i + 1][j + 1] = if a[i] == b[j] {
+                lcs[i][j] + 1
+            } else {
+                lcs[i][j + 1].max(lcs[i + 1][j])
+            };
+        }
+    }
+    2.0 * lcs[a.len()][b.len()] as f64 / (a.len() + b.len()) as f64
+}
+
+/// Ranks `entries` by how closely their signatures match `query`.
+pub fn search<'a>(query: &str, entries: &'a [Entry]) -> Vec<(&'a Entry, f64)> {
+    let query = normalize(query);
+    let mut results: Vec<(&Entry, f64)> = entries
+        .iter()
+        .filter_map(|entry| {
+            let signature = normalize(entry.signature.as_deref()?);
+            Some((entry, similarity(&query, &signature)))
+        })
+        .filter(|(_, score)| *score >= MIN_SCORE)
+        .collect();
+    results.sort_by(|a, b| b.1.total_cmp(&a.1));
+    results.truncate(MAX_RESULTS);
+    results
+}
+
+pub fn run_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let query = args.join(" ");
+    if query.trim().is_empty() {
+        return Err("usage: /dhall-search <type>, e.g. List a -> Optional a".to_string());
+    }
+    let version = prelude::docs_version(worktree)?;
+    let entries = docs::prelude::entries(&version)?;
+
+    let mut text = format!("Prelude {version} functions matching `{query}`:\n\n");
+    let results = search(&query, &entries);
+    if results.is_empty() {
+        text.push_str("No matches.\n");
+    }
+    for (entry, _) in results {
+        text.push_str(&format!(
+            "- `{}` : `{}`\n",
+            entry.name,
+            entry.signature.as_deref().unwrap_or_default()
+        ));
+        if let Some(summary) = entry.description.as_deref().and_then(|d| d.lines().next()) {
+            text.push_str(&format!("  {summary}\n"));
+        }
+    }
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: format!("Prelude search: {query}"),
+        }],
+        text,
+    })
+}
@@ -0,0 +1,255 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use zed_extension_api::{self as zed, Result, SlashCommandOutput, SlashCommandOutputSection};
+
+use crate::{cli, docs, imports, worktree};
+
+pub const SLASH_COMMAND: &str = "dhall-vendor";
+
+/// Where remote imports are vendored, relative to the worktree root. Each
+/// file is stored at its URL without the `https://` scheme, so relative
+/// imports between vendored files keep working unchanged.
+pub const VENDOR_DIR: &str = "vendor/dhall";
+
+/// Where plain `http://` imports are vendored, so that they are restored
+/// with the scheme they had.
+const HTTP_VENDOR_DIR: &str = "vendor/dhall-http";
+
+fn is_vendored(file: &str) -> bool {
+    file.starts_with(&format!("{VENDOR_DIR}/")) || file.starts_with(&format!("{HTTP_VENDOR_DIR}/"))
+}
+
+fn is_remote(target: &str) -> bool {
+    target.starts_with("https://") || target.starts_with("http://")
+}
+
+fn vendored_path(url: &str) -> Option<String> {
+    let (scheme, path) = url.split_once("://")?;
+    let dir = if scheme == "http" {
+        HTTP_VENDOR_DIR
+    } else {
+        VENDOR_DIR
+    };
+    let path = path.split(['?', '#']).next()?;
+    if path
+        .split('/')
+        .any(|segment| segment == ".." || segment.is_empty())
+    {
+        return None;
+    }
+    Some(format!("{dir}/{path}"))
+}
+
+#[derive(Default)]
+struct Vendor {
+    /// Vendored URLs and their worktree-relative paths.
+    files: HashMap<String, String>,
+    /// The vendored files' paths and contents, to be written once every
+    /// download has succeeded.
+    contents: Vec<(String, String)>,
+}
+
+impl Vendor {
+    /// Downloads `url` and everything it imports, returning its path.
+    fn vendor(&mut self, url: &str) -> Result<String> {
+        if let Some(path) = self.files.get(url) {
+            return Ok(path.clone());
+        }
+        let path = vendored_path(url).ok_or_else(|| format!("cannot vendor {url}"))?;
+        self.files.insert(url.to_string(), path.clone());
+        let source = docs::fetch_text(url)?;
+
+        let mut remote = HashMap::new();
+        for import in imports::imports(&source) {
+            if is_remote(&import.target) {
+                let vendored = self.vendor(&import.target)?;
+                remote.insert(import.target, vendored);
+            } else if import.target.starts_with("./") || import.target.starts_with("../") {
+                let url = docs::join_url(url, &import.target)
+                    .ok_or_else(|| format!("cannot resolve {} from {url}", import.target))?;
+                self.vendor(&url)?;
+            }
+        }
+        let source = imports::rewrite_imports(&source, |import| {
+            let target = remote.get(&import.target)?;
+            Some(imports::relative_import(&path, target))
+        })
+        .unwrap_or(source);
+        self.contents.push((path.clone(), source));
+        Ok(path)
+    }
+}
+
+/// Vendors the remote imports of `files`, pointing them at the local copies.
+/// Integrity checks are kept, since the copies are identical. Nothing is
+/// written until every import has been downloaded.
+pub fn vendor(root: &Path, files: &[String]) -> Result<Vec<String>> {
+    let mut vendor = Vendor::default();
+    let mut rewrites = Vec::new();
+    for file in files {
+        let path = root.join(file);
+        let source =
+            fs::read_to_string(&path).map_err(|e| format!("failed to read {file}: {e}"))?;
+        let mut vendored = HashMap::new();
+        for import in imports::imports(&source) {
+            if is_remote(&import.target) {
+                let local = vendor.vendor(&import.target)?;
+                vendored.insert(import.target, local);
+            }
+        }
+        let rewritten = imports::rewrite_imports(&source, |import| {
+            Some(imports::relative_import(
+                file,
+                vendored.get(&import.target)?,
+            ))
+        });
+        if let Some(rewritten) = rewritten {
+            rewrites.push((file.clone(), rewritten));
+        }
+    }
+    for (path, _) in &vendor.contents {
+        if let Some(dir) = root.join(path).parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("failed to create {}: {e}", dir.display()))?;
+        }
+    }
+    // The vendored files go first, so that no file is pointed at a copy that
+    // hasn't been written.
+    let updated = rewrites.iter().map(|(file, _)| file.clone()).collect();
+    vendor.contents.extend(rewrites);
+    worktree::write_files(root, &vendor.contents)?;
+    Ok(updated)
+}
+
+/// Points imports of vendored files in `files` back at their URLs.
+pub fn restore(root: &Path, files: &[String]) -> Result<Vec<String>> {
+    let https = format!("{VENDOR_DIR}/");
+    let http = format!("{HTTP_VENDOR_DIR}/");
+    let mut rewrites = Vec::new();
+    for file in files {
+        let path = root.join(file);
+        let source =
+            fs::read_to_string(&path).map_err(|e| format!("failed to read {file}: {e}"))?;
+        let rewritten = imports::rewrite_imports(&source, |import| {
+            let target = imports::resolve_local(file, &import.target)?;
+            match target.strip_prefix(&http) {
+                Some(path) => Some(format!("http://{path}")),
+                None => Some(format!("https://{}", target.strip_prefix(&https)?)),
+            }
+        });
+        if let Some(rewritten) = rewritten {
+            rewrites.push((file.clone(), rewritten));
+        }
+    }
+    worktree::write_files(root, &rewrites)?;
+    Ok(rewrites.into_iter().map(|(file, _)| file).collect())
+}
+
+/// Freezes and vendors any remote imports in the worktree that aren't yet
+/// vendored, for `/dhall-vendor new`. Freezing needs `dhall` on the `PATH`,
+/// and is skipped without it.
+fn vendor_new(worktree: &zed::Worktree) -> Result<Vec<String>> {
+    let root = PathBuf::from(worktree.root_path());
+    let files: Vec<String> = worktree::dhall_files(&root)
+        .into_iter()
+        .filter(|file| !is_vendored(file))
+        .filter(|file| {
+            fs::read_to_string(root.join(file)).is_ok_and(|source| {
+                imports::imports(&source)
+                    .iter()
+                    .any(|import| is_remote(&import.target))
+            })
+        })
+        .collect();
+    if files.is_empty() {
+        return Ok(files);
+    }
+    if let Ok(dhall) = cli::which(worktree, "dhall") {
+        for file in &files {
+            let output = cli::run(&dhall, &["freeze", "--inplace", file], &root, None)?;
+            if !output.success {
+                return Err(format!("failed to freeze {file}: {}", output.stderr.trim()));
+            }
+        }
+    }
+    vendor(&root, &files)
+}
+
+pub fn run_slash_command(
+    args: Vec<String>,
+    worktree: &zed::Worktree,
+) -> Result<SlashCommandOutput> {
+    let root = PathBuf::from(worktree.root_path());
+    let (restoring, file) = match args.as_slice() {
+        [command] if command == "new" => {
+            let updated = vendor_new(worktree)?;
+            let mut text = format!(
+                "Froze and vendored new remote imports into {VENDOR_DIR} in {} files\n",
+                updated.len()
+            );
+            for file in &updated {
+                text.push_str(&format!("- {file}\n"));
+            }
+            return Ok(SlashCommandOutput {
+                sections: vec![SlashCommandOutputSection {
+                    range: (0..text.len()).into(),
+                    label: "Vendored imports".to_string(),
+                }],
+                text,
+            });
+        }
+        [command, rest @ ..] if command == "restore" => (true, rest.first()),
+        [file] => (false, Some(file)),
+        [] => (false, None),
+        _ => return Err("usage: /dhall-vendor [new | restore] [file]".to_string()),
+    };
+    let files: Vec<String> = match file {
+        Some(file) => vec![file.trim_start_matches("./").to_string()],
+        None => worktree::dhall_files(&root)
+            .into_iter()
+            .filter(|file| !is_vendored(file))
+            .collect(),
+    };
+
+    let updated = if restoring {
+        restore(&root, &files)?
+    } else {
+        vendor(&root, &files)?
+    };
+    let mut text = if restoring {
+        format!("Restored remote imports in {} files\n", updated.len())
+    } else {
+        format!(
+            "Vendored remote imports into {VENDOR_DIR} in {} files\n",
+            updated.len()
+        )
+    };
+    for file in &updated {
+        text.push_str(&format!("- {file}\n"));
+    }
+    Ok(SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: "Vendored imports".to_string(),
+        }],
+        text,
+    })
+}
@@ -0,0 +1,65 @@
+/*
+   Copyright 2024 Sean Cribbs
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Finds the `.dhall` files beneath `root`, relative to `root`, skipping
+/// hidden directories.
+pub fn dhall_files(root: &Path) -> Vec<String> {
+    let mut files = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(root.join(&dir)) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+            let path = dir.join(&name);
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                pending.push(path);
+            } else if name.ends_with(".dhall") {
+                files.push(path.to_string_lossy().replace('\\', "/"));
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Writes each file's new contents, relative to `root`. Callers compute
+/// every file's contents before writing any, so that a failed lookup can't
+/// leave a project half-rewritten; if a write fails, the error names the
+/// files already written.
+pub fn write_files(root: &Path, files: &[(String, String)]) -> Result<(), String> {
+    for (ix, (file, contents)) in files.iter().enumerate() {
+        if let Err(e) = fs::write(root.join(file), contents) {
+            let written: Vec<&str> = files[..ix].iter().map(|(file, _)| file.as_str()).collect();
+            return Err(if written.is_empty() {
+                format!("failed to write {file}: {e}")
+            } else {
+                format!(
+                    "failed to write {file}: {e}; already rewrote {}",
+                    written.join(", ")
+                )
+            });
+        }
+    }
+    Ok(())
+}